@@ -0,0 +1,115 @@
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    minimum: Point3,
+    maximum: Point3,
+}
+
+impl Aabb {
+    /// Create a box spanning the two corners, in any order
+    ///
+    /// Flat boxes are padded slightly so that rays grazing them still register
+    /// a hit in the slab test.
+    pub fn new(a: Point3, b: Point3) -> Aabb {
+        const MIN_EXTENT: f64 = 1e-4;
+
+        let mut minimum = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let mut maximum = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+        for axis in 0..3 {
+            if maximum[axis] - minimum[axis] < MIN_EXTENT {
+                minimum[axis] -= MIN_EXTENT / 2.0;
+                maximum[axis] += MIN_EXTENT / 2.0;
+            }
+        }
+
+        Aabb { minimum, maximum }
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.minimum
+    }
+
+    pub fn max(&self) -> Point3 {
+        self.maximum
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    /// Index of the axis (0 for x, 1 for y, 2 for z) along which the box is widest
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        let dir = r.direction();
+        let inv_dir = Vec3::new(1.0 / dir.x(), 1.0 / dir.y(), 1.0 / dir.z());
+        self.hit_inv(r.origin(), inv_dir, t_min, t_max)
+    }
+
+    /// Slab test with a precomputed reciprocal ray direction
+    pub fn hit_inv(&self, origin: Point3, inv_dir: Vec3, t_min: f64, t_max: f64) -> bool {
+        self.clip_inv(origin, inv_dir, t_min, t_max).is_some()
+    }
+
+    /// Part of the range `t_min..t_max` over which the ray is inside the box
+    pub fn clip(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let dir = r.direction();
+        let inv_dir = Vec3::new(1.0 / dir.x(), 1.0 / dir.y(), 1.0 / dir.z());
+        self.clip_inv(r.origin(), inv_dir, t_min, t_max)
+    }
+
+    fn clip_inv(
+        &self,
+        origin: Point3,
+        inv_dir: Vec3,
+        mut t_min: f64,
+        mut t_max: f64,
+    ) -> Option<(f64, f64)> {
+        for axis in 0..3 {
+            let mut t0 = (self.minimum[axis] - origin[axis]) * inv_dir[axis];
+            let mut t1 = (self.maximum[axis] - origin[axis]) * inv_dir[axis];
+            if inv_dir[axis] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // Written so that a NaN slab (origin on a face of a flat axis) is ignored
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
+
+/// Smallest box enclosing both boxes
+pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
+    let small = Point3::new(
+        box0.min().x().min(box1.min().x()),
+        box0.min().y().min(box1.min().y()),
+        box0.min().z().min(box1.min().z()),
+    );
+    let big = Point3::new(
+        box0.max().x().max(box1.max().x()),
+        box0.max().y().max(box1.max().y()),
+        box0.max().z().max(box1.max().z()),
+    );
+    Aabb::new(small, big)
+}
@@ -0,0 +1,266 @@
+use crate::camera::CameraParams;
+use crate::transform::Mat4;
+use crate::vec3::{Point3, Vec3};
+
+/// How a camera moves between keyframes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    /// In straight lines, changing direction sharply at each keyframe
+    Linear,
+    /// Along a Catmull-Rom spline, a smooth curve through every keyframe
+    CatmullRom,
+}
+
+/// Where a camera is and what it looks at on one frame
+#[derive(Clone, Copy)]
+pub struct CameraKeyframe {
+    pub frame: f64,
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vfov: f64,
+    pub focus_dist: f64,
+}
+
+impl CameraKeyframe {
+    pub fn new(
+        frame: f64,
+        lookfrom: Point3,
+        lookat: Point3,
+        vfov: f64,
+        focus_dist: f64,
+    ) -> CameraKeyframe {
+        CameraKeyframe {
+            frame,
+            lookfrom,
+            lookat,
+            vfov,
+            focus_dist,
+        }
+    }
+}
+
+/// A camera keyframed over a sequence of frames
+///
+/// Frames between keyframes, including fractional ones, are interpolated;
+/// before the first keyframe and after the last the camera holds still.
+pub struct CameraAnimation {
+    keyframes: Vec<CameraKeyframe>,
+    interpolation: Interpolation,
+}
+
+impl CameraAnimation {
+    /// Animate a camera through the given keyframes
+    ///
+    /// # Arguments
+    /// * `keyframes` - The camera's placements, in any order
+    /// * `interpolation` - How to move between them
+    ///
+    /// # Panics
+    /// If there are no keyframes.
+    pub fn new(
+        mut keyframes: Vec<CameraKeyframe>,
+        interpolation: Interpolation,
+    ) -> CameraAnimation {
+        assert!(!keyframes.is_empty(), "an animation needs a keyframe");
+        keyframes.sort_by(|a, b| a.frame.total_cmp(&b.frame));
+        CameraAnimation {
+            keyframes,
+            interpolation,
+        }
+    }
+
+    /// A turntable: the camera circles `lookat` around the `vup` axis,
+    /// starting from `lookfrom`, turning by `degrees` over frames 1 to
+    /// `frames`
+    ///
+    /// Each frame turns by the same angle, so for a full turn frame
+    /// `frames + 1` would be back at the start and the sequence loops.
+    pub fn orbit(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        vfov: f64,
+        degrees: f64,
+        frames: usize,
+    ) -> CameraAnimation {
+        let frames = frames.max(1);
+        let focus_dist = (lookfrom - lookat).length();
+        let keyframes = (0..frames)
+            .map(|k| {
+                let turn = Mat4::rotation(vup, degrees * k as f64 / frames as f64);
+                let position = lookat + turn.transform_vector(lookfrom - lookat);
+                CameraKeyframe::new((k + 1) as f64, position, lookat, vfov, focus_dist)
+            })
+            .collect();
+        CameraAnimation::new(keyframes, Interpolation::Linear)
+    }
+
+    /// Frames of the first and last keyframes
+    pub fn frame_range(&self) -> (f64, f64) {
+        let keys = &self.keyframes;
+        (keys[0].frame, keys[keys.len() - 1].frame)
+    }
+
+    /// Camera placement on the given frame
+    pub fn at(&self, frame: f64) -> CameraKeyframe {
+        let keys = &self.keyframes;
+        let next = keys.partition_point(|k| k.frame <= frame);
+        if next == 0 {
+            return keys[0];
+        }
+        if next == keys.len() {
+            return keys[next - 1];
+        }
+
+        // Blend the keyframes on either side, and for a spline one more on
+        // each side (or the end keyframe again where there is none)
+        let index = [
+            next.saturating_sub(2),
+            next - 1,
+            next,
+            (next + 1).min(keys.len() - 1),
+        ];
+        let [k0, k1, k2, k3] = index.map(|i| &keys[i]);
+        let u = (frame - k1.frame) / (k2.frame - k1.frame);
+        let weights = match self.interpolation {
+            Interpolation::Linear => [0.0, 1.0 - u, u, 0.0],
+            Interpolation::CatmullRom => {
+                // Cubic Hermite curve with tangents from the neighbouring
+                // keyframes, scaled for keyframes unevenly spaced in time
+                let a = (k2.frame - k1.frame) / (k2.frame - k0.frame);
+                let b = (k2.frame - k1.frame) / (k3.frame - k1.frame);
+                let h00 = (1.0 + 2.0 * u) * (1.0 - u) * (1.0 - u);
+                let h10 = u * (1.0 - u) * (1.0 - u);
+                let h01 = u * u * (3.0 - 2.0 * u);
+                let h11 = u * u * (u - 1.0);
+                [-h10 * a, h00 - h11 * b, h10 * a + h01, h11 * b]
+            }
+        };
+
+        let keys = [k0, k1, k2, k3];
+        let blend = |value: fn(&CameraKeyframe) -> f64| -> f64 {
+            keys.iter().zip(weights).map(|(k, w)| w * value(k)).sum()
+        };
+        let blend_point = |value: fn(&CameraKeyframe) -> Point3| -> Point3 {
+            keys.iter()
+                .zip(weights)
+                .fold(Point3::default(), |sum, (k, w)| sum + w * value(k))
+        };
+        CameraKeyframe {
+            frame,
+            lookfrom: blend_point(|k| k.lookfrom),
+            lookat: blend_point(|k| k.lookat),
+            vfov: blend(|k| k.vfov),
+            focus_dist: blend(|k| k.focus_dist),
+        }
+    }
+
+    /// `params` with the camera placed as on the given frame
+    pub fn apply(&self, params: &CameraParams, frame: f64) -> CameraParams {
+        let key = self.at(frame);
+        CameraParams {
+            lookfrom: key.lookfrom,
+            lookat: key.lookat,
+            vfov: key.vfov,
+            focus_dist: Some(key.focus_dist),
+            ..*params
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Point3, b: Point3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    fn keyframes() -> Vec<CameraKeyframe> {
+        // Unevenly spaced, and out of order
+        vec![
+            CameraKeyframe::new(
+                4.0,
+                Point3::new(3.0, 1.0, 0.0),
+                Point3::new(0.0, 0.0, 1.0),
+                30.0,
+                2.0,
+            ),
+            CameraKeyframe::new(
+                1.0,
+                Point3::new(0.0, 0.0, 5.0),
+                Point3::new(0.0, 0.0, 0.0),
+                40.0,
+                5.0,
+            ),
+            CameraKeyframe::new(
+                10.0,
+                Point3::new(-2.0, 4.0, 1.0),
+                Point3::new(1.0, 0.0, 0.0),
+                60.0,
+                3.0,
+            ),
+            CameraKeyframe::new(
+                5.0,
+                Point3::new(5.0, 2.0, -3.0),
+                Point3::new(0.0, 1.0, 0.0),
+                45.0,
+                4.0,
+            ),
+        ]
+    }
+
+    #[test]
+    fn interpolation_passes_through_keyframes() {
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            let animation = CameraAnimation::new(keyframes(), interpolation);
+            assert_eq!(animation.frame_range(), (1.0, 10.0));
+            for key in keyframes() {
+                let at = animation.at(key.frame);
+                assert_near(at.lookfrom, key.lookfrom);
+                assert_near(at.lookat, key.lookat);
+                assert!((at.vfov - key.vfov).abs() < 1e-9);
+                assert!((at.focus_dist - key.focus_dist).abs() < 1e-9);
+            }
+
+            // Holding still outside the keyframes
+            assert_near(animation.at(-3.0).lookfrom, Point3::new(0.0, 0.0, 5.0));
+            assert_near(animation.at(12.5).lookfrom, Point3::new(-2.0, 4.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn catmull_rom_is_smooth_and_linear_is_straight() {
+        let linear = CameraAnimation::new(keyframes(), Interpolation::Linear);
+        assert_near(linear.at(2.5).lookfrom, Point3::new(1.5, 0.5, 2.5));
+
+        // Approaching a keyframe from either side, the spline moves at the
+        // same velocity (per frame), unlike straight lines
+        let spline = CameraAnimation::new(keyframes(), Interpolation::CatmullRom);
+        let h = 1e-5;
+        for frame in [4.0, 5.0] {
+            let before = (spline.at(frame).lookfrom - spline.at(frame - h).lookfrom) / h;
+            let after = (spline.at(frame + h).lookfrom - spline.at(frame).lookfrom) / h;
+            assert!((before - after).length() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn orbit_keeps_its_radius() {
+        let lookat = Point3::new(1.0, 2.0, 3.0);
+        let lookfrom = Point3::new(4.0, 6.0, 3.0);
+        let animation =
+            CameraAnimation::orbit(lookfrom, lookat, Vec3::new(0.0, 1.0, 0.0), 35.0, 360.0, 8);
+        assert_eq!(animation.frame_range(), (1.0, 8.0));
+        assert_near(animation.at(1.0).lookfrom, lookfrom);
+        // A quarter turn around the y axis
+        assert_near(animation.at(3.0).lookfrom, Point3::new(1.0, 6.0, 0.0));
+        for frame in 1..=8 {
+            let key = animation.at(frame as f64);
+            assert!(((key.lookfrom - lookat).length() - 5.0).abs() < 1e-9);
+            assert!((key.lookfrom.y() - 6.0).abs() < 1e-9);
+            assert!((key.focus_dist - 5.0).abs() < 1e-9);
+            assert_near(key.lookat, lookat);
+        }
+    }
+}
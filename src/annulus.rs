@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::common::{self, Rng};
+use crate::disk;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::plane;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+/// A flat ring: a disk with a round hole in the middle
+pub struct Annulus {
+    center: Point3,
+    inner_radius: f64,
+    outer_radius: f64,
+    mat: Arc<dyn Material>,
+    uvw: Onb,
+}
+
+impl Annulus {
+    /// Create a ring
+    ///
+    /// # Arguments
+    /// * `center` - The center of the ring
+    /// * `normal` - The normal vector (perpendicular to the ring)
+    /// * `inner_radius` - The radius of the hole
+    /// * `outer_radius` - The outer radius of the ring
+    /// * `mat` - The material of the ring
+    ///
+    /// # Panics
+    /// If the two radii are the same, which leaves no ring, or `normal` is the
+    /// zero vector.
+    pub fn new(
+        center: Point3,
+        normal: Vec3,
+        inner_radius: f64,
+        outer_radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> Annulus {
+        let (inner_radius, outer_radius) = (inner_radius.abs(), outer_radius.abs());
+        assert!(inner_radius != outer_radius, "an annulus needs a width");
+        assert!(normal.length_squared() > 0.0, "an annulus needs a normal");
+        Annulus {
+            center,
+            inner_radius: inner_radius.min(outer_radius),
+            outer_radius: inner_radius.max(outer_radius),
+            mat,
+            uvw: Onb::new(normal),
+        }
+    }
+}
+
+impl Hittable for Annulus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let Some(t) = plane::intersect(r, self.center, self.uvw.w(), t_min, t_max) else {
+            return false;
+        };
+        let p = r.at(t);
+        let (u, distance) = disk::polar_uv(&self.uvw, p - self.center);
+        if distance < self.inner_radius || distance > self.outer_radius {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        // `v` runs from the inner edge to the outer edge
+        rec.u = u;
+        rec.v = (distance - self.inner_radius) / (self.outer_radius - self.inner_radius);
+        rec.set_face_normal(r, self.uvw.w());
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(disk::bounding_box(
+            self.center,
+            self.uvw.w(),
+            self.outer_radius,
+        ))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let area = common::PI
+            * (self.outer_radius * self.outer_radius - self.inner_radius * self.inner_radius);
+        plane::solid_angle_pdf(self, area, origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        // Uniform over the area: the squared distance is uniform between the radii
+        let inner_squared = self.inner_radius * self.inner_radius;
+        let outer_squared = self.outer_radius * self.outer_radius;
+        let distance = f64::sqrt(common::random_double_range(
+            rng,
+            inner_squared,
+            outer_squared,
+        ));
+        let angle = 2.0 * common::PI * common::random_double(rng);
+        let p = self.center + distance * (angle.cos() * self.uvw.u() + angle.sin() * self.uvw.v());
+        p - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::vec3;
+
+    // Ring from radius 0.5 to 1 at y = 1, facing down
+    fn ring() -> Annulus {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Annulus::new(
+            Point3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            0.5,
+            1.0,
+            mat,
+        )
+    }
+
+    fn hit_at(shape: &dyn Hittable, x: f64, z: f64) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(x, 0.0, z), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        shape
+            .hit(&r, 0.001, common::INFINITY, &mut rec)
+            .then_some(rec)
+    }
+
+    #[test]
+    fn hit_and_uv() {
+        let ring = ring();
+        let rec = hit_at(&ring, 0.0, 0.75).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        // `v` runs from the inner edge to the outer edge
+        assert!((rec.v - 0.5).abs() < 1e-9);
+        assert!((hit_at(&ring, 0.0, 0.99).unwrap().v - 0.98).abs() < 1e-9);
+        let opposite = hit_at(&ring, 0.0, -0.75).unwrap();
+        assert!(((opposite.u - rec.u).rem_euclid(1.0) - 0.5).abs() < 1e-9);
+
+        // Through the hole and outside the rim
+        assert!(hit_at(&ring, 0.2, 0.2).is_none());
+        assert!(hit_at(&ring, 0.8, 0.8).is_none());
+    }
+
+    #[test]
+    fn pdf_matches_sampling() {
+        let ring = ring();
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        assert_eq!(ring.pdf_value(origin, Vec3::new(0.0, 1.0, 0.0)), 0.0);
+        // Distance and cosine to a point at radius 0.75, over the ring's area
+        let d2: f64 = 1.0 + 0.75 * 0.75;
+        let expected = d2 * d2.sqrt() / (common::PI * 0.75);
+        let pdf = ring.pdf_value(origin, Vec3::new(0.75, 1.0, 0.0));
+        assert!((pdf - expected).abs() < 1e-9);
+
+        let mut rng = common::pixel_rng(6, 0);
+        for _ in 0..1000 {
+            assert!(ring.pdf_value(origin, ring.random(origin, &mut rng)) > 0.0);
+        }
+        let n = 200_000;
+        let total: f64 = (0..n)
+            .map(|_| ring.pdf_value(origin, vec3::random_unit_vector(&mut rng)))
+            .sum();
+        let integral = 4.0 * common::PI * total / n as f64;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+    }
+}
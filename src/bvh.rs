@@ -0,0 +1,369 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::common::Rng;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// Number of buckets used to evaluate the surface area heuristic
+const SAH_BUCKETS: usize = 12;
+// Leaves never hold more primitives than this
+const MAX_LEAF_SIZE: usize = 4;
+// Cost of visiting an interior node relative to one primitive intersection
+const TRAVERSAL_COST: f64 = 0.125;
+// Below this depth splits fall back to halving, keeping the traversal stack bounded
+const MAX_SAH_DEPTH: usize = 40;
+
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Point3,
+}
+
+struct LinearNode {
+    bounds: Aabb,
+    // Leaf: index of the first primitive. Interior: index of the second child,
+    // the first child always directly follows its parent
+    offset: usize,
+    // Number of primitives in a leaf, 0 for interior nodes
+    count: usize,
+    // Split axis of an interior node
+    axis: usize,
+}
+
+/// Bounding volume hierarchy over the objects of a `HittableList`
+///
+/// The tree is built top-down with a binned surface area heuristic and stored
+/// as a flat array in depth-first order. Unbounded objects such as infinite
+/// planes cannot be placed in the tree and are tested separately on every ray.
+pub struct BvhNode {
+    nodes: Vec<LinearNode>,
+    objects: Vec<Arc<dyn Hittable>>,
+    unbounded: Vec<Arc<dyn Hittable>>,
+    bounds: Option<Aabb>,
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> BvhNode {
+        let source = list.objects();
+        let mut items = Vec::with_capacity(source.len());
+        let mut unbounded = Vec::new();
+        for (index, object) in source.iter().enumerate() {
+            match object.bounding_box() {
+                Some(bounds) => items.push(BuildItem {
+                    index,
+                    bounds,
+                    centroid: bounds.centroid(),
+                }),
+                None => unbounded.push(object.clone()),
+            }
+        }
+
+        let mut bvh = BvhNode {
+            nodes: Vec::with_capacity(2 * items.len()),
+            objects: Vec::with_capacity(items.len()),
+            bounds: None,
+            unbounded,
+        };
+        if !items.is_empty() {
+            bvh.build(&mut items, source, 0);
+            if bvh.unbounded.is_empty() {
+                bvh.bounds = Some(bvh.nodes[0].bounds);
+            }
+        }
+        bvh
+    }
+
+    // Append the subtree over `items` to the node array, returning its index
+    fn build(
+        &mut self,
+        items: &mut [BuildItem],
+        source: &[Arc<dyn Hittable>],
+        depth: usize,
+    ) -> usize {
+        let node_index = self.nodes.len();
+        let bounds = items.iter().skip(1).fold(items[0].bounds, |b, item| {
+            aabb::surrounding_box(b, item.bounds)
+        });
+        self.nodes.push(LinearNode {
+            bounds,
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+
+        let split = if depth < MAX_SAH_DEPTH {
+            Self::find_split(items, bounds)
+        } else if items.len() > MAX_LEAF_SIZE {
+            Some((0, items.len() / 2))
+        } else {
+            None
+        };
+
+        match split {
+            Some((axis, mid)) => {
+                let (left, right) = items.split_at_mut(mid);
+                self.build(left, source, depth + 1);
+                let second = self.build(right, source, depth + 1);
+                let node = &mut self.nodes[node_index];
+                node.offset = second;
+                node.axis = axis;
+            }
+            None => {
+                let node = &mut self.nodes[node_index];
+                node.offset = self.objects.len();
+                node.count = items.len();
+                for item in items.iter() {
+                    self.objects.push(source[item.index].clone());
+                }
+            }
+        }
+
+        node_index
+    }
+
+    // Partition `items` along the cheapest split, or return `None` to make a leaf
+    fn find_split(items: &mut [BuildItem], bounds: Aabb) -> Option<(usize, usize)> {
+        let n = items.len();
+        if n == 1 {
+            return None;
+        }
+
+        let centroid_bounds = items.iter().skip(1).fold(
+            Aabb::new(items[0].centroid, items[0].centroid),
+            |b, item| aabb::surrounding_box(b, Aabb::new(item.centroid, item.centroid)),
+        );
+        let axis = centroid_bounds.longest_axis();
+        let lo = centroid_bounds.min()[axis];
+        let extent = centroid_bounds.max()[axis] - lo;
+        if extent <= 1e-9 {
+            // All centroids coincide, so no split can separate them
+            if n <= MAX_LEAF_SIZE {
+                return None;
+            }
+            return Some((axis, n / 2));
+        }
+
+        let bucket_of = |c: Point3| {
+            usize::min(
+                ((c[axis] - lo) / extent * SAH_BUCKETS as f64) as usize,
+                SAH_BUCKETS - 1,
+            )
+        };
+
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut boxes: [Option<Aabb>; SAH_BUCKETS] = [None; SAH_BUCKETS];
+        for item in items.iter() {
+            let b = bucket_of(item.centroid);
+            counts[b] += 1;
+            boxes[b] = Some(match boxes[b] {
+                Some(existing) => aabb::surrounding_box(existing, item.bounds),
+                None => item.bounds,
+            });
+        }
+
+        // Cost of splitting after each bucket
+        let mut best_cost = f64::INFINITY;
+        let mut best_bucket = 0;
+        for split in 0..SAH_BUCKETS - 1 {
+            let (count_a, area_a) = Self::bucket_range(&counts[..=split], &boxes[..=split]);
+            let (count_b, area_b) = Self::bucket_range(&counts[split + 1..], &boxes[split + 1..]);
+            if count_a == 0 || count_b == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (count_a as f64 * area_a + count_b as f64 * area_b) / bounds.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                best_bucket = split;
+            }
+        }
+
+        if n <= MAX_LEAF_SIZE && best_cost >= n as f64 {
+            return None;
+        }
+
+        let mut mid = 0;
+        for i in 0..n {
+            if bucket_of(items[i].centroid) <= best_bucket {
+                items.swap(i, mid);
+                mid += 1;
+            }
+        }
+        if mid == 0 || mid == n {
+            mid = n / 2;
+        }
+        Some((axis, mid))
+    }
+
+    fn bucket_range(counts: &[usize], boxes: &[Option<Aabb>]) -> (usize, f64) {
+        let count = counts.iter().sum();
+        let bounds = boxes
+            .iter()
+            .flatten()
+            .copied()
+            .reduce(aabb::surrounding_box);
+        (count, bounds.map_or(0.0, |b| b.surface_area()))
+    }
+
+    // Closest hit of any object, each tested by `hit_object` against the
+    // closest distance found so far
+    fn closest_hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        mut hit_object: impl FnMut(&dyn Hittable, f64, &mut HitRecord) -> bool,
+    ) -> bool {
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
+        for object in &self.unbounded {
+            // A fresh record for each object, so that fields one shape does not
+            // set, such as `trap`, cannot carry over from another
+            let mut temp_rec = HitRecord::new();
+            if hit_object(&**object, closest_so_far, &mut temp_rec) {
+                hit_anything = true;
+                closest_so_far = temp_rec.t;
+                *rec = temp_rec;
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return hit_anything;
+        }
+
+        let origin = r.origin();
+        let dir = r.direction();
+        let inv_dir = Vec3::new(1.0 / dir.x(), 1.0 / dir.y(), 1.0 / dir.z());
+        let dir_is_neg = [inv_dir.x() < 0.0, inv_dir.y() < 0.0, inv_dir.z() < 0.0];
+
+        let mut stack = [0usize; 64];
+        let mut stack_len = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bounds.hit_inv(origin, inv_dir, t_min, closest_so_far) {
+                if node.count > 0 {
+                    for object in &self.objects[node.offset..node.offset + node.count] {
+                        let mut temp_rec = HitRecord::new();
+                        if hit_object(&**object, closest_so_far, &mut temp_rec) {
+                            hit_anything = true;
+                            closest_so_far = temp_rec.t;
+                            *rec = temp_rec;
+                        }
+                    }
+                } else {
+                    // Visit the child nearer to the ray origin first
+                    let (near, far) = if dir_is_neg[node.axis] {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+
+        hit_anything
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.closest_hit(r, t_min, t_max, rec, |object, t_max, rec| {
+            object.hit(r, t_min, t_max, rec)
+        })
+    }
+
+    fn sample_hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut Rng,
+    ) -> bool {
+        self.closest_hit(r, t_min, t_max, rec, |object, t_max, rec| {
+            object.sample_hit(r, t_min, t_max, rec, rng)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::common;
+    use crate::material::Lambertian;
+    use crate::plane::Plane;
+    use crate::quad::Quad;
+    use crate::sphere::Sphere;
+    use crate::vec3;
+
+    // Spheres and quads scattered through a box, above an unbounded plane
+    fn scene(rng: &mut Rng) -> HittableList {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+        list.add(Arc::new(Plane::new(
+            Point3::new(0.0, -10.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            mat.clone(),
+        )));
+        for k in 0..300 {
+            let center = Vec3::random_range(rng, -8.0, 8.0);
+            if k % 3 == 0 {
+                let u = Vec3::random_range(rng, -1.0, 1.0);
+                let v = Vec3::random_range(rng, -1.0, 1.0);
+                list.add(Arc::new(Quad::new(center, u, v, mat.clone())));
+            } else {
+                let radius = common::random_double_range(rng, 0.05, 0.8);
+                list.add(Arc::new(Sphere::new(center, radius, mat.clone())));
+            }
+        }
+        list
+    }
+
+    #[test]
+    fn hits_match_linear_search() {
+        let mut rng = common::pixel_rng(7, 0);
+        let list = scene(&mut rng);
+        let bvh = BvhNode::new(scene(&mut common::pixel_rng(7, 0)));
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = Vec3::random_range(&mut rng, -12.0, 12.0);
+            let direction = vec3::random_unit_vector(&mut rng);
+            let r = Ray::new(origin, direction, 0.0);
+            let t_max = common::random_double_range(&mut rng, 1.0, 30.0);
+
+            let (mut expected, mut actual) = (HitRecord::new(), HitRecord::new());
+            let hit = list.hit(&r, 0.001, t_max, &mut expected);
+            assert_eq!(bvh.hit(&r, 0.001, t_max, &mut actual), hit);
+            if hit {
+                hits += 1;
+                assert_eq!(actual.t, expected.t);
+                assert_eq!(actual.front_face, expected.front_face);
+                assert!((actual.normal - expected.normal).length() < 1e-12);
+            }
+        }
+        // Enough rays hit for the comparison to mean something
+        assert!(hits > 500, "{}", hits);
+    }
+}
@@ -1,48 +1,806 @@
-use crate::common;
+use crate::common::{self, Rng};
 use crate::ray::Ray;
+use crate::stereo::{StereoCamera, StereoLayout};
 use crate::vec3::{self, Point3, Vec3};
- 
-pub struct Camera {
+
+/// Turns points on the image into rays into the scene
+pub trait Camera: Send + Sync {
+    /// Ray through the point (`s`, `t`) of the image, where both run from 0
+    /// to 1 starting at the bottom left corner; `None` where the camera sees
+    /// nothing, such as outside the circle of a fisheye image
+    fn get_ray(&self, s: f64, t: f64, rng: &mut Rng) -> Option<Ray>;
+}
+
+/// How a camera maps directions onto the image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// An ordinary pinhole or thin-lens camera
+    Perspective,
+    /// Parallel rays, showing a view `height` units tall
+    Orthographic { height: f64 },
+    /// A circular fisheye image covering `fov` degrees across
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    /// The full sphere of directions, for 360 degree panoramas
+    Equirectangular,
+}
+
+/// Placement of a camera, independent of the image it renders into
+#[derive(Clone, Copy)]
+pub struct CameraParams {
+    pub projection: Projection,
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub vfov: f64, // Vertical field-of-view in degrees
+    // Diameter of the lens, 0 for a pinhole camera that keeps everything sharp
+    pub aperture: f64,
+    // Distance to the plane in focus; `None` focuses on `lookat`
+    pub focus_dist: Option<f64>,
+    // Number of aperture blades (0 for a round aperture) and their rotation
+    // in degrees
+    pub blades: usize,
+    pub blade_rotation: f64,
+    // Times the shutter opens and closes; objects that move in between blur
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    // Render a pair of views, one for each eye, laid out in one image;
+    // orthographic cameras have no stereo and ignore it
+    pub stereo: Option<StereoLayout>,
+    // Distance between the eyes
+    pub interocular: f64,
+    // Distance at which the eyes' views line up; `None` uses the focus
+    // distance. Only perspective cameras converge
+    pub convergence: Option<f64>,
+}
+
+impl CameraParams {
+    pub fn build(&self, aspect_ratio: f64) -> Box<dyn Camera> {
+        match self.stereo {
+            None => self.build_eye(aspect_ratio, 0.0),
+            Some(layout) => {
+                let aspect_ratio = layout.eye_aspect_ratio(aspect_ratio);
+                let half = self.interocular / 2.0;
+                Box::new(StereoCamera::new(
+                    self.build_eye(aspect_ratio, -half),
+                    self.build_eye(aspect_ratio, half),
+                    layout,
+                ))
+            }
+        }
+    }
+
+    // Camera for an eye `offset` to the right of `lookfrom`
+    fn build_eye(&self, aspect_ratio: f64, offset: f64) -> Box<dyn Camera> {
+        let (from, at, up) = (self.lookfrom, self.lookat, self.vup);
+        let (open, close) = (self.shutter_open, self.shutter_close);
+        let focus_dist = self.focus_dist.unwrap_or_else(|| (from - at).length());
+        match self.projection {
+            Projection::Perspective => Box::new(
+                PerspectiveCamera::new(from, at, up, self.vfov, aspect_ratio)
+                    .with_lens(self.aperture, focus_dist)
+                    .with_blades(self.blades, self.blade_rotation)
+                    .with_eye(offset, self.convergence.unwrap_or(focus_dist))
+                    .with_shutter(open, close),
+            ),
+            Projection::Orthographic { height } => Box::new(
+                OrthographicCamera::new(from, at, up, height, aspect_ratio)
+                    .with_shutter(open, close),
+            ),
+            Projection::Fisheye { fov, mapping } => Box::new(
+                FisheyeCamera::new(from, at, up, fov, mapping, aspect_ratio)
+                    .with_eye(offset)
+                    .with_shutter(open, close),
+            ),
+            Projection::Equirectangular => Box::new(
+                EquirectangularCamera::new(from, at, up)
+                    .with_eye(offset)
+                    .with_shutter(open, close),
+            ),
+        }
+    }
+}
+
+// Camera axes looking from `lookfrom` to `lookat`: right, up and backwards
+fn basis(lookfrom: Point3, lookat: Point3, vup: Vec3) -> (Vec3, Vec3, Vec3) {
+    let w = vec3::unit_vector(lookfrom - lookat);
+    let u = vec3::unit_vector(vec3::cross(vup, w));
+    let v = vec3::cross(w, u);
+    (u, v, w)
+}
+
+// Interval the shutter is open for, over which ray times are spread evenly
+#[derive(Clone, Copy)]
+struct Shutter {
+    open: f64,
+    close: f64,
+}
+
+impl Shutter {
+    const INSTANT: Shutter = Shutter {
+        open: 0.0,
+        close: 0.0,
+    };
+
+    fn time(&self, rng: &mut Rng) -> f64 {
+        common::random_double_range(rng, self.open, self.close)
+    }
+}
+
+/// A perspective camera: a pinhole, or a thin lens with depth of field
+pub struct PerspectiveCamera {
     origin: Point3,
+    // The image, on the plane in focus
     lower_left_corner: Point3,
     horizontal: Vec3,
     vertical: Vec3,
+    // Camera axes: right, up and backwards
+    u: Vec3,
+    v: Vec3,
+    focus_dist: f64,
+    lookat_dist: f64,
+    lens_radius: f64,
+    // Aperture shape: a circle, or a polygon when there are 3 or more blades
+    blades: usize,
+    blade_rotation: f64,
+    // Offset of the eye from `origin` in a stereo rig, and the distance at
+    // which it converges with the other eye
+    eye_offset: Vec3,
+    convergence: f64,
+    shutter: Shutter,
 }
- 
-impl Camera {
+
+impl PerspectiveCamera {
     pub fn new(
         lookfrom: Point3,
         lookat: Point3,
         vup: Vec3,
         vfov: f64, // Vertical field-of-view in degrees
         aspect_ratio: f64,
-    ) -> Camera {
+    ) -> PerspectiveCamera {
         let theta = common::degrees_to_radians(vfov);
         let h = f64::tan(theta / 2.0);
         let viewport_height = 2.0 * h;
         let viewport_width = aspect_ratio * viewport_height;
- 
-        let w = vec3::unit_vector(lookfrom - lookat);
-        let u = vec3::unit_vector(vec3::cross(vup, w));
-        let v = vec3::cross(w, u);
- 
+
+        let (u, v, w) = basis(lookfrom, lookat, vup);
         let origin = lookfrom;
         let horizontal = viewport_width * u;
         let vertical = viewport_height * v;
         let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - w;
- 
-        Camera {
+
+        PerspectiveCamera {
             origin,
             lower_left_corner,
             horizontal,
             vertical,
+            u,
+            v,
+            focus_dist: 1.0,
+            lookat_dist: (lookfrom - lookat).length(),
+            lens_radius: 0.0,
+            blades: 0,
+            blade_rotation: 0.0,
+            eye_offset: Vec3::new(0.0, 0.0, 0.0),
+            convergence: common::INFINITY,
+            shutter: Shutter::INSTANT,
         }
     }
- 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        Ray::new(
-            self.origin,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin,
+
+    /// Give the camera a lens, so that only things at one distance are sharp
+    ///
+    /// # Arguments
+    /// * `aperture` - The diameter of the lens; 0 keeps everything sharp
+    /// * `focus_dist` - The distance from the camera to the plane in focus
+    pub fn with_lens(mut self, aperture: f64, focus_dist: f64) -> PerspectiveCamera {
+        // Move the image out to the plane in focus, where rays through every
+        // point of the lens meet
+        let scale = focus_dist / self.focus_dist;
+        self.horizontal = scale * self.horizontal;
+        self.vertical = scale * self.vertical;
+        self.lower_left_corner = self.origin + scale * (self.lower_left_corner - self.origin);
+        self.focus_dist = focus_dist;
+        self.lens_radius = aperture / 2.0;
+        self
+    }
+
+    /// Give the camera a lens focused on its `lookat` point
+    pub fn with_autofocus(self, aperture: f64) -> PerspectiveCamera {
+        let focus_dist = self.lookat_dist;
+        self.with_lens(aperture, focus_dist)
+    }
+
+    /// Shape the aperture as a regular polygon, as the blades of a real lens
+    /// do, which shapes out-of-focus highlights (bokeh) the same way. Fewer
+    /// than 3 blades give a round aperture
+    ///
+    /// # Arguments
+    /// * `blades` - The number of blades, and sides of the polygon
+    /// * `rotation` - The angle in degrees the polygon is turned by
+    pub fn with_blades(mut self, blades: usize, rotation: f64) -> PerspectiveCamera {
+        self.blades = blades;
+        self.blade_rotation = common::degrees_to_radians(rotation);
+        self
+    }
+
+    /// Make the camera one eye of a stereo pair
+    ///
+    /// The eye sits `offset` to the right of `lookfrom` (negative for the
+    /// left eye) and looks the same way, with its image shifted sideways so
+    /// that the two eyes' views line up at the `convergence` distance. Nearer
+    /// things appear in front of the screen, farther ones behind it.
+    ///
+    /// # Arguments
+    /// * `offset` - Half the distance between the eyes, signed by side
+    /// * `convergence` - The distance that appears at the screen
+    pub fn with_eye(mut self, offset: f64, convergence: f64) -> PerspectiveCamera {
+        self.eye_offset = offset * self.u;
+        self.convergence = convergence;
+        self
+    }
+
+    /// Keep the shutter open from `open` to `close`, so that moving objects
+    /// blur along their path; rays are spread evenly over that time
+    pub fn with_shutter(mut self, open: f64, close: f64) -> PerspectiveCamera {
+        self.shutter = Shutter { open, close };
+        self
+    }
+
+    // Random point in the aperture, scaled to fit in the unit disk
+    fn random_in_aperture(&self, rng: &mut Rng) -> Vec3 {
+        if self.blades < 3 {
+            return vec3::random_in_unit_disk(rng);
+        }
+
+        // The polygon is made of equal triangles between the center and each
+        // pair of neighbouring corners: pick one, then a point within it
+        let side = common::random_int(rng, 0, self.blades);
+        let corner = |k: usize| {
+            let angle = self.blade_rotation + 2.0 * common::PI * k as f64 / self.blades as f64;
+            Vec3::new(angle.cos(), angle.sin(), 0.0)
+        };
+        let mut a = common::random_double(rng);
+        let mut b = common::random_double(rng);
+        if a + b > 1.0 {
+            a = 1.0 - a;
+            b = 1.0 - b;
+        }
+        a * corner(side) + b * corner(side + 1)
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut Rng) -> Option<Ray> {
+        let offset = if self.lens_radius > 0.0 {
+            let p = self.lens_radius * self.random_in_aperture(rng);
+            p.x() * self.u + p.y() * self.v
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        };
+
+        // The image, on the plane in focus, moves with the eye by less the
+        // nearer that plane is than the plane of convergence, so the eyes'
+        // views of that plane coincide
+        let origin = self.origin + self.eye_offset;
+        let shift = (1.0 - self.focus_dist / self.convergence) * self.eye_offset;
+        let target = self.lower_left_corner + s * self.horizontal + t * self.vertical + shift;
+        Some(Ray::new(
+            origin + offset,
+            target - origin - offset,
+            self.shutter.time(rng),
+        ))
+    }
+}
+
+/// A camera whose rays all run parallel, so that objects keep their size at
+/// any distance, as in technical and architectural drawings
+pub struct OrthographicCamera {
+    lower_left_corner: Point3,
+    horizontal: Vec3,
+    vertical: Vec3,
+    direction: Vec3,
+    shutter: Shutter,
+}
+
+impl OrthographicCamera {
+    /// Create an orthographic camera
+    ///
+    /// # Arguments
+    /// * `lookfrom` - The center of the view
+    /// * `lookat` - A point the camera looks towards
+    /// * `vup` - The direction that is up in the image
+    /// * `height` - The height of the view in world units
+    /// * `aspect_ratio` - The width of the image divided by its height
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        height: f64,
+        aspect_ratio: f64,
+    ) -> OrthographicCamera {
+        let (u, v, w) = basis(lookfrom, lookat, vup);
+        let horizontal = aspect_ratio * height * u;
+        let vertical = height * v;
+        OrthographicCamera {
+            lower_left_corner: lookfrom - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            direction: -w,
+            shutter: Shutter::INSTANT,
+        }
+    }
+
+    /// Keep the shutter open from `open` to `close`
+    pub fn with_shutter(mut self, open: f64, close: f64) -> OrthographicCamera {
+        self.shutter = Shutter { open, close };
+        self
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut Rng) -> Option<Ray> {
+        Some(Ray::new(
+            self.lower_left_corner + s * self.horizontal + t * self.vertical,
+            self.direction,
+            self.shutter.time(rng),
+        ))
+    }
+}
+
+/// How a fisheye lens spaces directions across its image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FisheyeMapping {
+    /// Distance from the center proportional to the angle off the axis
+    Equidistant,
+    /// Equal areas of the image cover equal solid angles
+    Equisolid,
+}
+
+/// A circular fisheye camera, seeing up to all around itself in one disk
+///
+/// The disk fills the height of the image; beyond it the camera sees nothing.
+pub struct FisheyeCamera {
+    origin: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    // Half the field of view, in radians
+    half_fov: f64,
+    mapping: FisheyeMapping,
+    aspect_ratio: f64,
+    shutter: Shutter,
+}
+
+impl FisheyeCamera {
+    /// Create a fisheye camera
+    ///
+    /// # Arguments
+    /// * `lookfrom` - Where the camera is
+    /// * `lookat` - The point at the center of the image
+    /// * `vup` - The direction that is up in the image
+    /// * `fov` - The angle in degrees across the disk, up to 360
+    /// * `mapping` - How angles are spaced across the disk
+    /// * `aspect_ratio` - The width of the image divided by its height
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        fov: f64,
+        mapping: FisheyeMapping,
+        aspect_ratio: f64,
+    ) -> FisheyeCamera {
+        let (u, v, w) = basis(lookfrom, lookat, vup);
+        FisheyeCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            half_fov: common::degrees_to_radians(fov.clamp(0.0, 360.0)) / 2.0,
+            mapping,
+            aspect_ratio,
+            shutter: Shutter::INSTANT,
+        }
+    }
+
+    /// Keep the shutter open from `open` to `close`
+    pub fn with_shutter(mut self, open: f64, close: f64) -> FisheyeCamera {
+        self.shutter = Shutter { open, close };
+        self
+    }
+}
+
+impl FisheyeCamera {
+    /// Move the camera `offset` to the right, as one of a pair of parallel
+    /// eyes for stereo dome images
+    pub fn with_eye(mut self, offset: f64) -> FisheyeCamera {
+        self.origin += offset * self.u;
+        self
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut Rng) -> Option<Ray> {
+        // Position in the disk, which has radius 1
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+        let r = f64::sqrt(x * x + y * y);
+        if r > 1.0 {
+            return None;
+        }
+
+        // Angle off the axis
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => {
+                2.0 * f64::asin(common::clamp(r * f64::sin(self.half_fov / 2.0), -1.0, 1.0))
+            }
+        };
+        let phi = f64::atan2(y, x);
+        let direction =
+            theta.sin() * (phi.cos() * self.u + phi.sin() * self.v) - theta.cos() * self.w;
+        Some(Ray::new(self.origin, direction, self.shutter.time(rng)))
+    }
+}
+
+/// A camera seeing in every direction, laid out by longitude across the
+/// image and latitude up it, as 360 degree panoramas are
+///
+/// The middle of the image looks towards `lookat`. The image should be twice
+/// as wide as it is tall for pixels to cover equal angles both ways.
+pub struct EquirectangularCamera {
+    origin: Point3,
+    // Horizontal axes, to the right of and straight ahead of `lookat`
+    right: Vec3,
+    forward: Vec3,
+    up: Vec3,
+    // Radius of the circle an ODS eye moves around, signed by side
+    eye_offset: f64,
+    shutter: Shutter,
+}
+
+impl EquirectangularCamera {
+    /// Create a panoramic camera
+    ///
+    /// # Arguments
+    /// * `lookfrom` - Where the camera is
+    /// * `lookat` - The point whose direction is at the middle of the image
+    /// * `vup` - The direction of the top edge of the image, the zenith
+    pub fn new(lookfrom: Point3, lookat: Point3, vup: Vec3) -> EquirectangularCamera {
+        // Latitude is measured from the plane square to `vup`, so `lookat`
+        // only sets which way is straight ahead within it
+        let up = vec3::unit_vector(vup);
+        let (right, _, _) = basis(lookfrom, lookat, up);
+        let forward = vec3::cross(up, right);
+        EquirectangularCamera {
+            origin: lookfrom,
+            right,
+            forward,
+            up,
+            eye_offset: 0.0,
+            shutter: Shutter::INSTANT,
+        }
+    }
+
+    /// Make the camera one eye of an omni-directional stereo (ODS) pair
+    ///
+    /// Rather than sitting still, the eye circles `lookfrom` at a distance of
+    /// `offset`, always to the right of the direction it looks in (to the left
+    /// for a negative offset), so every column of the panorama sees the scene
+    /// as a pair of eyes turned that way would.
+    pub fn with_eye(mut self, offset: f64) -> EquirectangularCamera {
+        self.eye_offset = offset;
+        self
+    }
+
+    /// Keep the shutter open from `open` to `close`
+    pub fn with_shutter(mut self, open: f64, close: f64) -> EquirectangularCamera {
+        self.shutter = Shutter { open, close };
+        self
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut Rng) -> Option<Ray> {
+        let longitude = (s - 0.5) * 2.0 * common::PI;
+        let latitude = (t - 0.5) * common::PI;
+        let direction = latitude.cos()
+            * (longitude.sin() * self.right + longitude.cos() * self.forward)
+            + latitude.sin() * self.up;
+        // To the right of the horizontal direction the eye looks in
+        let eye = self.eye_offset * (longitude.cos() * self.right - longitude.sin() * self.forward);
+        Some(Ray::new(
+            self.origin + eye,
+            direction,
+            self.shutter.time(rng),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_direction(r: &Ray, expected: Vec3) {
+        let d = vec3::unit_vector(r.direction());
+        let e = vec3::unit_vector(expected);
+        assert!((d - e).length() < 1e-9, "{} != {}", d, e);
+    }
+
+    fn perspective() -> PerspectiveCamera {
+        // Looking down -z with a 90 degree view, so the image edges are at 45
+        // degrees
+        PerspectiveCamera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -10.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
         )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn pinhole_directions() {
+        let cam = perspective();
+        let mut rng = common::pixel_rng(1, 0);
+        let ray = |s, t, rng: &mut Rng| cam.get_ray(s, t, rng).unwrap();
+        assert_direction(&ray(0.5, 0.5, &mut rng), Vec3::new(0.0, 0.0, -1.0));
+        assert_direction(&ray(0.5, 1.0, &mut rng), Vec3::new(0.0, 1.0, -1.0));
+        assert_direction(&ray(0.0, 0.5, &mut rng), Vec3::new(-2.0, 0.0, -1.0));
+        assert_direction(&ray(1.0, 0.0, &mut rng), Vec3::new(2.0, -1.0, -1.0));
+    }
+
+    #[test]
+    fn thin_lens_rays_meet_on_the_focus_plane() {
+        let cam = perspective().with_lens(2.0, 4.0);
+        let mut rng = common::pixel_rng(2, 0);
+        for (s, t) in [(0.5, 0.5), (0.2, 0.9), (1.0, 0.0)] {
+            // Where the pinhole ray through this point crosses z = -4
+            let d = perspective().get_ray(s, t, &mut rng).unwrap().direction();
+            let focus = -4.0 / d.z() * d;
+            for _ in 0..100 {
+                let r = cam.get_ray(s, t, &mut rng).unwrap();
+                // Leaves from within the lens...
+                assert!(r.origin().z().abs() < 1e-12);
+                assert!(r.origin().length() <= 1.0 + 1e-12);
+                // ...and passes through the same point in focus
+                let k = -4.0 / r.direction().z();
+                assert!((r.origin() + k * r.direction() - focus).length() < 1e-9);
+            }
+        }
+
+        // Autofocus focuses on `lookat`
+        let cam = perspective().with_autofocus(1.0);
+        assert!((cam.focus_dist - 10.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn polygonal_aperture_samples_stay_in_the_polygon() {
+        let mut rng = common::pixel_rng(3, 0);
+        for (blades, rotation) in [(3, 0.0), (5, 18.0), (6, 30.0)] {
+            let cam = perspective().with_blades(blades, rotation);
+            let n = blades as f64;
+            // Distance from the center to the middle of each side
+            let apothem = (common::PI / n).cos();
+            let mut reach: f64 = 0.0;
+            for _ in 0..2000 {
+                let p = cam.random_in_aperture(&mut rng);
+                assert_eq!(p.z(), 0.0);
+                for k in 0..blades {
+                    let angle = common::degrees_to_radians(rotation)
+                        + (2.0 * k as f64 + 1.0) * common::PI / n;
+                    let side = Vec3::new(angle.cos(), angle.sin(), 0.0);
+                    assert!(vec3::dot(p, side) <= apothem + 1e-12);
+                }
+                reach = reach.max(p.length());
+            }
+            // Samples get close to the corners, on the unit circle
+            assert!(reach > 0.9 && reach <= 1.0 + 1e-12);
+        }
+    }
+
+    fn params(projection: Projection) -> CameraParams {
+        CameraParams {
+            projection,
+            lookfrom: Point3::new(1.0, 2.0, 3.0),
+            lookat: Point3::new(4.0, 2.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 40.0,
+            aperture: 0.0,
+            focus_dist: None,
+            blades: 0,
+            blade_rotation: 0.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            stereo: None,
+            interocular: 0.0,
+            convergence: None,
+        }
+    }
+
+    #[test]
+    fn image_center_looks_at_lookat() {
+        let equidistant = FisheyeMapping::Equidistant;
+        let equisolid = FisheyeMapping::Equisolid;
+        let mut rng = common::pixel_rng(4, 0);
+        for projection in [
+            Projection::Perspective,
+            Projection::Orthographic { height: 2.0 },
+            Projection::Fisheye {
+                fov: 180.0,
+                mapping: equidistant,
+            },
+            Projection::Fisheye {
+                fov: 360.0,
+                mapping: equisolid,
+            },
+            Projection::Equirectangular,
+        ] {
+            let p = params(projection);
+            let r = p.build(1.5).get_ray(0.5, 0.5, &mut rng).unwrap();
+            assert_direction(&r, p.lookat - p.lookfrom);
+            assert!((r.origin() - p.lookfrom).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let cam = OrthographicCamera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            2.0,
+        );
+        let mut rng = common::pixel_rng(5, 0);
+        let r = cam.get_ray(0.0, 1.0, &mut rng).unwrap();
+        assert_direction(&r, Vec3::new(0.0, 0.0, -1.0));
+        assert!((r.origin() - Point3::new(-2.0, 1.0, 0.0)).length() < 1e-12);
+    }
+
+    // Angle in degrees between a ray and the -z axis
+    fn angle_off_axis(r: &Ray) -> f64 {
+        let cos = vec3::dot(vec3::unit_vector(r.direction()), Vec3::new(0.0, 0.0, -1.0));
+        cos.clamp(-1.0, 1.0).acos().to_degrees()
+    }
+
+    #[test]
+    fn fisheye_mappings() {
+        let fisheye = |fov, mapping| {
+            FisheyeCamera::new(
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, -1.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                fov,
+                mapping,
+                2.0,
+            )
+        };
+        let mut rng = common::pixel_rng(6, 0);
+        for fov in [120.0, 180.0, 270.0] {
+            for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+                let cam = fisheye(fov, mapping);
+                // The edge of the disk is at half the field of view, whichever
+                // way round it
+                for (s, t) in [(0.5, 1.0), (0.5, 0.0), (0.25, 0.5), (0.75, 0.5)] {
+                    let r = cam.get_ray(s, t, &mut rng).unwrap();
+                    assert!((angle_off_axis(&r) - fov / 2.0).abs() < 1e-9);
+                }
+                // Beyond the disk the camera sees nothing
+                assert!(cam.get_ray(0.0, 0.0, &mut rng).is_none());
+                assert!(cam.get_ray(0.5, 1.01, &mut rng).is_none());
+            }
+        }
+
+        // Halfway to the edge, straight up
+        let half = 0.75;
+        let r = fisheye(180.0, FisheyeMapping::Equidistant)
+            .get_ray(0.5, half, &mut rng)
+            .unwrap();
+        assert_direction(&r, Vec3::new(0.0, 1.0, -1.0));
+        let r = fisheye(180.0, FisheyeMapping::Equisolid)
+            .get_ray(0.5, half, &mut rng)
+            .unwrap();
+        let theta = 2.0 * (0.5 * 45f64.to_radians().sin()).asin();
+        assert!((angle_off_axis(&r) - theta.to_degrees()).abs() < 1e-9);
+        assert!(r.direction().y() > 0.0 && r.direction().x().abs() < 1e-12);
+    }
+
+    #[test]
+    fn equirectangular_directions() {
+        let cam = EquirectangularCamera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let mut rng = common::pixel_rng(7, 0);
+        for (s, t, expected) in [
+            (0.5, 0.5, Vec3::new(0.0, 0.0, -1.0)),
+            (0.75, 0.5, Vec3::new(1.0, 0.0, 0.0)),
+            (0.25, 0.5, Vec3::new(-1.0, 0.0, 0.0)),
+            (0.0, 0.5, Vec3::new(0.0, 0.0, 1.0)),
+            (1.0, 0.5, Vec3::new(0.0, 0.0, 1.0)),
+            (0.5, 0.75, Vec3::new(0.0, 1.0, -1.0)),
+            (0.625, 0.25, Vec3::new(1.0, -f64::sqrt(2.0), -1.0)),
+            (0.3, 1.0, Vec3::new(0.0, 1.0, 0.0)),
+        ] {
+            assert_direction(&cam.get_ray(s, t, &mut rng).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn stereo_eyes_converge() {
+        let mut rng = common::pixel_rng(8, 0);
+        // Converging at 5 while focused at 2, or never converging
+        for (convergence, focus_dist) in [(5.0, 2.0), (5.0, 5.0), (common::INFINITY, 3.0)] {
+            let eye = |offset| {
+                perspective()
+                    .with_lens(0.0, focus_dist)
+                    .with_eye(offset, convergence)
+            };
+            let (left, right) = (eye(-0.1), eye(0.1));
+            let l = left.get_ray(0.5, 0.5, &mut rng).unwrap();
+            let r = right.get_ray(0.5, 0.5, &mut rng).unwrap();
+            assert!((l.origin() - Point3::new(-0.1, 0.0, 0.0)).length() < 1e-12);
+            assert!((r.origin() - Point3::new(0.1, 0.0, 0.0)).length() < 1e-12);
+
+            if convergence.is_finite() {
+                // Both views' centers cross the axis at the convergence distance
+                for ray in [&l, &r] {
+                    let k = -convergence / ray.direction().z();
+                    assert!(ray.at(k).x().abs() < 1e-12);
+                }
+            } else {
+                assert_direction(&l, Vec3::new(0.0, 0.0, -1.0));
+                assert_direction(&r, Vec3::new(0.0, 0.0, -1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn stereo_rig_offsets_each_eye() {
+        let mut rng = common::pixel_rng(9, 0);
+        let mut p = params(Projection::Perspective);
+        p.stereo = Some(StereoLayout::SideBySide);
+        p.interocular = 0.2;
+        let cam = p.build(2.0);
+        let (u, _, _) = basis(p.lookfrom, p.lookat, p.vup);
+        let l = cam.get_ray(0.25, 0.5, &mut rng).unwrap();
+        let r = cam.get_ray(0.75, 0.5, &mut rng).unwrap();
+        assert!((l.origin() - (p.lookfrom - 0.1 * u)).length() < 1e-12);
+        assert!((r.origin() - (p.lookfrom + 0.1 * u)).length() < 1e-12);
+        // Converging on `lookat` by default
+        for ray in [&l, &r] {
+            let to = p.lookat - ray.origin();
+            assert!(vec3::cross(vec3::unit_vector(ray.direction()), to).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn ods_eyes_circle_square_to_the_view() {
+        let mut rng = common::pixel_rng(10, 0);
+        let eye = |offset| {
+            EquirectangularCamera::new(
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, -1.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            )
+            .with_eye(offset)
+        };
+        let (left, right) = (eye(-0.1), eye(0.1));
+        // Looking ahead, then to the right, then behind
+        for (s, side) in [
+            (0.5, Vec3::new(1.0, 0.0, 0.0)),
+            (0.75, Vec3::new(0.0, 0.0, 1.0)),
+            (0.0, Vec3::new(-1.0, 0.0, 0.0)),
+        ] {
+            for t in [0.2, 0.5, 0.9] {
+                let l = left.get_ray(s, t, &mut rng).unwrap();
+                let r = right.get_ray(s, t, &mut rng).unwrap();
+                assert!((l.origin() + 0.1 * side).length() < 1e-12);
+                assert!((r.origin() - 0.1 * side).length() < 1e-12);
+                // Both eyes look the same way, across the line between them
+                assert_direction(&l, r.direction());
+                assert!(vec3::dot(r.origin(), r.direction()).abs() < 1e-12);
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::cylinder;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+/// A cylinder closed by a hemisphere at each end: all points within `radius`
+/// of the segment between two points
+pub struct Capsule {
+    base: Point3,
+    axis: Vec3, // Unit vector from the base towards the top
+    height: f64,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl Capsule {
+    /// Create a capsule around the segment from `base` to `top`
+    ///
+    /// # Arguments
+    /// * `base` - The center of one hemisphere
+    /// * `top` - The center of the other hemisphere; the same point as
+    ///   `base` gives a sphere
+    /// * `radius` - The radius of the cylinder and both hemispheres
+    /// * `mat` - The material of the capsule
+    pub fn new(base: Point3, top: Point3, radius: f64, mat: Arc<dyn Material>) -> Capsule {
+        // The hemispheres of a capsule without length make a sphere whatever
+        // the axis
+        let axis = if (top - base).length_squared() == 0.0 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            vec3::unit_vector(top - base)
+        };
+        Capsule {
+            base,
+            axis,
+            height: (top - base).length(),
+            radius: radius.abs(),
+            mat,
+        }
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut consider = |t: f64, outward_normal: Vec3| {
+            if t > t_min && t < t_max && closest.is_none_or(|(best, _)| t < best) {
+                closest = Some((t, outward_normal));
+            }
+        };
+
+        // Cylindrical middle section
+        let oc = r.origin() - self.base;
+        let d_perp = r.direction() - vec3::dot(r.direction(), self.axis) * self.axis;
+        let oc_perp = oc - vec3::dot(oc, self.axis) * self.axis;
+        for t in cylinder::solve_quadratic(
+            d_perp.length_squared(),
+            vec3::dot(oc_perp, d_perp),
+            oc_perp.length_squared() - self.radius * self.radius,
+        )
+        .into_iter()
+        .flatten()
+        {
+            let p = r.at(t) - self.base;
+            let h = vec3::dot(p, self.axis);
+            if (0.0..=self.height).contains(&h) {
+                consider(t, (p - h * self.axis) / self.radius);
+            }
+        }
+
+        // Hemispheres, each keeping only the half beyond its end of the segment
+        let top = self.base + self.height * self.axis;
+        for (center, outward) in [(self.base, -self.axis), (top, self.axis)] {
+            let oc = r.origin() - center;
+            for t in cylinder::solve_quadratic(
+                r.direction().length_squared(),
+                vec3::dot(oc, r.direction()),
+                oc.length_squared() - self.radius * self.radius,
+            )
+            .into_iter()
+            .flatten()
+            {
+                let normal = (r.at(t) - center) / self.radius;
+                if vec3::dot(normal, outward) >= 0.0 {
+                    consider(t, normal);
+                }
+            }
+        }
+
+        let Some((t, outward_normal)) = closest else {
+            return false;
+        };
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, outward_normal);
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let top = self.base + self.height * self.axis;
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(aabb::surrounding_box(
+            Aabb::new(self.base - r, self.base + r),
+            Aabb::new(top - r, top + r),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::common;
+    use crate::material::Lambertian;
+
+    fn capsule(base: Point3, top: Point3) -> Capsule {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Capsule::new(base, top, 0.5, mat)
+    }
+
+    fn hit(shape: &dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        let r = Ray::new(origin, direction, 0.0);
+        shape
+            .hit(&r, 0.001, common::INFINITY, &mut rec)
+            .then_some(rec)
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn side_and_hemispheres() {
+        let upright = capsule(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0));
+        let rec = hit(
+            &upright,
+            Point3::new(5.0, 1.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert_near(rec.normal, Vec3::new(1.0, 0.0, 0.0));
+
+        // Onto the top hemisphere, off the axis
+        let rec = hit(
+            &upright,
+            Point3::new(0.3, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.p.y() - 2.4).abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(0.6, 0.8, 0.0));
+        let rec = hit(
+            &upright,
+            Point3::new(0.0, -5.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.p.y() + 0.5).abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+
+        // The parts of the end spheres inside the middle are not surface
+        let rec = hit(
+            &upright,
+            Point3::new(5.0, 0.25, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.p.x() - 0.5).abs() < 1e-9);
+        assert!(hit(
+            &upright,
+            Point3::new(5.0, 2.6, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn from_inside() {
+        let upright = capsule(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0));
+        let rec = hit(
+            &upright,
+            Point3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+        assert_near(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn without_length_is_a_sphere() {
+        let center = Point3::new(1.0, 1.0, 1.0);
+        let ball = capsule(center, center);
+        let directions = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 2.0, -2.0) / 3.0,
+        ];
+        for d in directions {
+            let rec = hit(&ball, center + 5.0 * d, -d).unwrap();
+            assert!((rec.t - 4.5).abs() < 1e-9);
+            assert!(rec.front_face);
+            assert_near(rec.normal, d);
+        }
+        let bbox = ball.bounding_box().unwrap();
+        assert!((bbox.min() - Point3::new(0.5, 0.5, 0.5)).length() < 1e-3);
+        assert!((bbox.max() - Point3::new(1.5, 1.5, 1.5)).length() < 1e-3);
+    }
+}
@@ -0,0 +1,424 @@
+use std::fmt;
+
+use ray_tracing::output::OutputFormat;
+use ray_tracing::png::BitDepth;
+
+pub const USAGE: &str = "\
+Usage: ray-tracing [OPTIONS] [SCENE]
+
+Renders SCENE (a scene file) or the built-in default scene.
+
+Options:
+  -W, --width N        Image width in pixels
+  -H, --height N       Image height in pixels
+  -a, --aspect RATIO   Aspect ratio, as a number or W:H (e.g. 16:9)
+  -s, --samples N      Samples per pixel
+  -d, --depth N        Maximum number of ray bounces
+  -o, --output PATH    Output file, '-' or no path writes to stdout; with
+                       --frames, the directory to write frames to
+  -f, --format FORMAT  Image format: p3, p6 (binary PPM), pfm (HDR) or png;
+                       by default chosen from the output file's extension
+                       (.ppm, .pfm, .png), or p3 for stdout and png for frames
+      --frames RANGE   Render frames FIRST-LAST (or a single frame N) of the
+                       scene's camera animation as frame_0001.png, ...
+      --bit-depth N    Bits per channel of PNG output, 8 or 16 (default 8)
+      --alpha          Add an alpha channel (object coverage) to PNG output
+      --seed N         Seed for the random number generator (random by default;
+                       the same seed always gives the same image)
+  -t, --threads N      Number of worker threads
+  -q, --quiet          Do not report progress
+  -v, --verbose        Report settings and timing as well as progress
+  -h, --help           Print this help
+";
+
+const VALUE_OPTIONS: &[&str] = &[
+    "-W",
+    "--width",
+    "-H",
+    "--height",
+    "-a",
+    "--aspect",
+    "-s",
+    "--samples",
+    "-d",
+    "--depth",
+    "-o",
+    "--output",
+    "-f",
+    "--format",
+    "--bit-depth",
+    "--frames",
+    "--seed",
+    "-t",
+    "--threads",
+];
+const FLAGS: &[&str] = &[
+    "--alpha",
+    "-q",
+    "--quiet",
+    "-v",
+    "--verbose",
+    "-h",
+    "--help",
+];
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+pub struct Args {
+    pub scene: Option<String>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub aspect_ratio: Option<f64>,
+    pub samples_per_pixel: Option<i32>,
+    pub max_depth: Option<i32>,
+    // `None` writes to stdout
+    pub output: Option<String>,
+    pub format: OutputFormat,
+    pub bit_depth: BitDepth,
+    pub alpha: bool,
+    // First and last frame of an animation to render
+    pub frames: Option<(usize, usize)>,
+    pub seed: Option<u64>,
+    pub threads: Option<usize>,
+    pub verbosity: Verbosity,
+    pub help: bool,
+}
+
+#[derive(Debug)]
+pub struct CliError(String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+fn parse_positive<T: std::str::FromStr + PartialOrd + Default>(
+    option: &str,
+    value: &str,
+) -> Result<T, CliError> {
+    match value.parse::<T>() {
+        Ok(n) if n > T::default() => Ok(n),
+        _ => Err(CliError(format!(
+            "{} expects a positive integer, got `{}`",
+            option, value
+        ))),
+    }
+}
+
+fn parse_frames(value: &str) -> Result<(usize, usize), CliError> {
+    let range = match value.split_once('-') {
+        Some((first, last)) => first.trim().parse().ok().zip(last.trim().parse().ok()),
+        None => value.trim().parse().ok().map(|n| (n, n)),
+    };
+    match range {
+        Some((first, last)) if first <= last => Ok((first, last)),
+        _ => Err(CliError(format!(
+            "--frames expects FIRST-LAST or a frame number, got `{}`",
+            value
+        ))),
+    }
+}
+
+fn parse_aspect(value: &str) -> Result<f64, CliError> {
+    let ratio = match value.split_once(':') {
+        Some((w, h)) => match (w.trim().parse::<f64>(), h.trim().parse::<f64>()) {
+            (Ok(w), Ok(h)) => w / h,
+            _ => f64::NAN,
+        },
+        None => value.parse::<f64>().unwrap_or(f64::NAN),
+    };
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(CliError(format!(
+            "--aspect expects a positive number or W:H, got `{}`",
+            value
+        )))
+    }
+}
+
+impl Args {
+    /// Parse the command line, not including the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, CliError> {
+        let mut parsed = Args {
+            scene: None,
+            width: None,
+            height: None,
+            aspect_ratio: None,
+            samples_per_pixel: None,
+            max_depth: None,
+            output: None,
+            format: OutputFormat::P3,
+            bit_depth: BitDepth::Eight,
+            alpha: false,
+            frames: None,
+            seed: None,
+            threads: None,
+            verbosity: Verbosity::Normal,
+            help: false,
+        };
+
+        let mut format = None;
+        let mut to_stdout = false;
+        let mut bit_depth = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Accept both `--option value` and `--option=value`
+            let (option, inline_value) = match arg.split_once('=') {
+                Some((o, v)) if o.starts_with("--") => (o.to_string(), Some(v.to_string())),
+                _ => (arg.clone(), None),
+            };
+
+            let is_flag = FLAGS.contains(&option.as_str());
+            let takes_value = VALUE_OPTIONS.contains(&option.as_str());
+            if option.starts_with('-') && option != "-" && !is_flag && !takes_value {
+                return Err(CliError(format!("unknown option `{}`", option)));
+            }
+            let value = if takes_value {
+                match inline_value.or_else(|| args.next()) {
+                    Some(v) => v,
+                    None => return Err(CliError(format!("{} expects a value", option))),
+                }
+            } else {
+                if inline_value.is_some() {
+                    return Err(CliError(format!("{} does not take a value", option)));
+                }
+                String::new()
+            };
+
+            match option.as_str() {
+                "-W" | "--width" => parsed.width = Some(parse_positive(&option, &value)?),
+                "-H" | "--height" => parsed.height = Some(parse_positive(&option, &value)?),
+                "-a" | "--aspect" => parsed.aspect_ratio = Some(parse_aspect(&value)?),
+                "-s" | "--samples" => {
+                    parsed.samples_per_pixel = Some(parse_positive(&option, &value)?)
+                }
+                "-d" | "--depth" => parsed.max_depth = Some(parse_positive(&option, &value)?),
+                "-o" | "--output" => {
+                    to_stdout = value == "-";
+                    parsed.output = Some(value).filter(|v| v != "-")
+                }
+                "-f" | "--format" => {
+                    format = Some(OutputFormat::from_name(&value).ok_or_else(|| {
+                        CliError(format!(
+                            "unknown format `{}` (supported: p3, p6, pfm, png)",
+                            value
+                        ))
+                    })?)
+                }
+                "--bit-depth" => {
+                    bit_depth = match value.as_str() {
+                        "8" => Some(BitDepth::Eight),
+                        "16" => Some(BitDepth::Sixteen),
+                        _ => {
+                            return Err(CliError(format!(
+                                "--bit-depth expects 8 or 16, got `{}`",
+                                value
+                            )))
+                        }
+                    }
+                }
+                "--alpha" => parsed.alpha = true,
+                "--frames" => parsed.frames = Some(parse_frames(&value)?),
+                "--seed" => {
+                    parsed.seed = Some(value.parse().map_err(|_| {
+                        CliError(format!("--seed expects an integer, got `{}`", value))
+                    })?)
+                }
+                "-t" | "--threads" => parsed.threads = Some(parse_positive(&option, &value)?),
+                "-q" | "--quiet" => parsed.verbosity = Verbosity::Quiet,
+                "-v" | "--verbose" => parsed.verbosity = Verbosity::Verbose,
+                "-h" | "--help" => parsed.help = true,
+                _ => {
+                    if parsed.scene.is_some() {
+                        return Err(CliError(format!("unexpected argument `{}`", arg)));
+                    }
+                    parsed.scene = Some(arg);
+                }
+            }
+        }
+
+        if parsed.width.is_some() && parsed.height.is_some() && parsed.aspect_ratio.is_some() {
+            return Err(CliError(
+                "give at most two of --width, --height and --aspect".to_string(),
+            ));
+        }
+
+        // Frames go into a directory, never to stdout or a single image file
+        if parsed.frames.is_some() {
+            if to_stdout {
+                return Err(CliError(
+                    "--frames cannot write to stdout, give -o a directory".to_string(),
+                ));
+            }
+            if let Some(path) = parsed.output.as_deref() {
+                if OutputFormat::from_path(path).is_some() {
+                    return Err(CliError(format!(
+                        "with --frames, -o names a directory, got the image file `{}`",
+                        path
+                    )));
+                }
+            }
+        }
+
+        parsed.format = match (format, &parsed.output) {
+            (Some(format), _) => format,
+            (None, _) if parsed.frames.is_some() => OutputFormat::Png,
+            (None, None) => OutputFormat::P3,
+            (None, Some(path)) => OutputFormat::from_path(path).ok_or_else(|| {
+                CliError(format!(
+                    "cannot tell the image format of `{}`, use --format",
+                    path
+                ))
+            })?,
+        };
+
+        // Only PNG has a choice of bit depth or an alpha channel
+        if parsed.format != OutputFormat::Png {
+            if bit_depth.is_some() {
+                return Err(CliError(
+                    "--bit-depth only applies to PNG output".to_string(),
+                ));
+            }
+            if parsed.alpha {
+                return Err(CliError("--alpha only applies to PNG output".to_string()));
+            }
+        }
+        parsed.bit_depth = bit_depth.unwrap_or(BitDepth::Eight);
+
+        Ok(parsed)
+    }
+
+    /// Final image size, starting from the scene's own size
+    pub fn image_size(&self, scene_width: usize, scene_height: usize) -> (usize, usize) {
+        let scene_aspect = scene_width as f64 / scene_height as f64;
+        let aspect = self.aspect_ratio.unwrap_or(scene_aspect);
+        let from_width = |w: usize| usize::max((w as f64 / aspect).round() as usize, 1);
+        let from_height = |h: usize| usize::max((h as f64 * aspect).round() as usize, 1);
+
+        match (self.width, self.height) {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, from_width(w)),
+            (None, Some(h)) => (from_height(h), h),
+            (None, None) if self.aspect_ratio.is_some() => (scene_width, from_width(scene_width)),
+            (None, None) => (scene_width, scene_height),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args, CliError> {
+        Args::parse(args.split_whitespace().map(String::from))
+    }
+
+    fn error(args: &str) -> String {
+        match parse(args) {
+            Ok(_) => panic!("`{}` should not parse", args),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn frames_write_to_a_directory() {
+        let args = parse("--frames 1-3 -o out").unwrap();
+        assert_eq!(args.output.as_deref(), Some("out"));
+        assert!(args.format == OutputFormat::Png);
+
+        assert!(error("--frames 1-3 -o -").contains("stdout"));
+        assert!(error("--frames 2 -o frame.png").contains("frame.png"));
+        assert!(error("-o out.PPM --frames 1-2").contains("out.PPM"));
+    }
+
+    #[test]
+    fn png_options_need_png_output() {
+        let args = parse("-o out.png --bit-depth 16 --alpha").unwrap();
+        assert!(args.bit_depth == BitDepth::Sixteen && args.alpha);
+        assert!(parse("--frames 1-2 --alpha").unwrap().alpha);
+        assert!(parse("-f png --bit-depth=8").unwrap().bit_depth == BitDepth::Eight);
+
+        assert!(error("--bit-depth 16").contains("--bit-depth"));
+        assert!(error("-o out.pfm --bit-depth 8").contains("--bit-depth"));
+        assert!(error("-o out.png -f p6 --alpha").contains("--alpha"));
+        assert!(error("--frames 1-2 -f p3 --alpha").contains("--alpha"));
+    }
+
+    #[test]
+    fn values_and_defaults() {
+        let args = parse("scene.toml -W 640 --height=360 -s 8 -d 4 --seed 7 -t 2 -q").unwrap();
+        assert_eq!(args.scene.as_deref(), Some("scene.toml"));
+        assert_eq!((args.width, args.height), (Some(640), Some(360)));
+        assert_eq!((args.samples_per_pixel, args.max_depth), (Some(8), Some(4)));
+        assert_eq!((args.seed, args.threads), (Some(7), Some(2)));
+        assert!(args.verbosity == Verbosity::Quiet);
+
+        let args = parse("").unwrap();
+        assert!(args.scene.is_none() && args.output.is_none() && args.frames.is_none());
+        assert!(args.format == OutputFormat::P3 && args.verbosity == Verbosity::Normal);
+        assert!(parse("-o -").unwrap().output.is_none());
+        assert!(parse("-o out.pfm").unwrap().format == OutputFormat::Pfm);
+        assert!(parse("-o out.pfm -f p6").unwrap().format == OutputFormat::P6);
+        assert!(parse("-h").unwrap().help);
+    }
+
+    #[test]
+    fn aspect_ratios() {
+        let aspect = |value: &str| parse(&format!("-a {}", value)).unwrap().aspect_ratio;
+        assert_eq!(aspect("16:9"), Some(16.0 / 9.0));
+        assert_eq!(aspect("1.5"), Some(1.5));
+        assert_eq!(aspect("2:1"), Some(2.0));
+        for bad in ["0", "-2", "16:0", "16:x", "wide", "nan", "inf"] {
+            assert!(error(&format!("--aspect {}", bad)).contains("--aspect"));
+        }
+
+        // The aspect ratio fills in whichever of width and height is missing
+        assert_eq!(
+            parse("-a 2 -W 300").unwrap().image_size(400, 225),
+            (300, 150)
+        );
+        assert_eq!(
+            parse("-a 2:1 -H 100").unwrap().image_size(400, 225),
+            (200, 100)
+        );
+        assert_eq!(parse("-a 4:1").unwrap().image_size(400, 225), (400, 100));
+        assert_eq!(parse("-W 800").unwrap().image_size(400, 225), (800, 450));
+        assert!(error("-W 1 -H 1 -a 1").contains("at most two"));
+    }
+
+    #[test]
+    fn frame_ranges() {
+        assert_eq!(parse("--frames 3-10").unwrap().frames, Some((3, 10)));
+        assert_eq!(parse("--frames=7").unwrap().frames, Some((7, 7)));
+        assert_eq!(parse("--frames 4-4").unwrap().frames, Some((4, 4)));
+        for bad in ["10-3", "a-b", "1-", "-3", "x"] {
+            assert!(error(&format!("--frames={}", bad)).contains("--frames"));
+        }
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert!(error("--bogus").contains("unknown option `--bogus`"));
+        assert!(error("-x 3").contains("unknown option `-x`"));
+        assert!(error("--width=").contains("--width"));
+        assert!(error("-W").contains("expects a value"));
+        assert!(error("-W 0").contains("positive integer"));
+        assert!(error("-s -4").contains("positive integer"));
+        assert!(error("--threads two").contains("positive integer"));
+        assert!(error("--seed -1").contains("--seed"));
+        assert!(error("--quiet=yes").contains("does not take a value"));
+        assert!(error("-f jpeg").contains("unknown format"));
+        assert!(error("--bit-depth 12").contains("8 or 16"));
+        assert!(error("-o out.jpg").contains("--format"));
+        assert!(error("a.toml b.toml").contains("unexpected argument `b.toml`"));
+    }
+}
@@ -1,29 +1,24 @@
-use std::io::Write;
- 
 use crate::common;
 use crate::vec3::Vec3;
- 
+
 // Type alias
 pub type Color = Vec3;
- 
-pub fn write_color(out: &mut impl Write, pixel_color: Color, samples_per_pixel: i32) {
-    let mut r = pixel_color.x();
-    let mut g = pixel_color.y();
-    let mut b = pixel_color.z();
- 
-    // Divide the color by the number of samples and gamma-correct for gamma=2.0
-    let scale = 1.0 / samples_per_pixel as f64;
-    r = f64::sqrt(scale * r);
-    g = f64::sqrt(scale * g);
-    b = f64::sqrt(scale * b);
- 
-    // Write the translated [0, 255] value of each color component
-    writeln!(
-        out,
-        "{} {} {}",
-        (256.0 * common::clamp(r, 0.0, 0.999)) as i32,
-        (256.0 * common::clamp(g, 0.0, 0.999)) as i32,
-        (256.0 * common::clamp(b, 0.0, 0.999)) as i32,
+
+/// Gamma-correct a linear color for gamma=2.0
+pub fn gamma_correct(linear: Color) -> Color {
+    Color::new(
+        f64::sqrt(linear.x().max(0.0)),
+        f64::sqrt(linear.y().max(0.0)),
+        f64::sqrt(linear.z().max(0.0)),
     )
-    .expect("writing color");
-}
\ No newline at end of file
+}
+
+/// Translate a display color component to the [0, 255] range
+pub fn to_u8(x: f64) -> u8 {
+    (256.0 * common::clamp(x, 0.0, 0.999)) as u8
+}
+
+/// Translate a display color component to the [0, 65535] range
+pub fn to_u16(x: f64) -> u16 {
+    (65536.0 * common::clamp(x, 0.0, 0.99999)) as u16
+}
@@ -1,26 +1,48 @@
-use rand::Rng;
- 
+use rand::{Rng as _, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
 // Constants
- 
+
 pub use std::f64::consts::PI;
-pub use std::f64::INFINITY;
- 
+pub const INFINITY: f64 = f64::INFINITY;
+
 // Utility functions
- 
+
 pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
+
+// Random numbers
+
+/// Random number generator used for all sampling
+///
+/// ChaCha produces the same sequence on every platform, so a render is
+/// reproducible from its seed.
+pub type Rng = ChaCha8Rng;
+
+/// Generator for one pixel: an independent stream of the render's seed, so
+/// its samples do not depend on the order in which pixels are rendered
+pub fn pixel_rng(seed: u64, pixel_index: u64) -> Rng {
+    let mut rng = Rng::seed_from_u64(seed);
+    rng.set_stream(pixel_index);
+    rng
+}
  
-pub fn random_double() -> f64 {
+pub fn random_double(rng: &mut Rng) -> f64 {
     // Return a random real in [0.0, 1.0)
-    rand::thread_rng().gen_range(0.0..1.0)
+    rng.gen_range(0.0..1.0)
 }
- 
-pub fn random_double_range(min: f64, max: f64) -> f64 {
+
+pub fn random_double_range(rng: &mut Rng, min: f64, max: f64) -> f64 {
     // Return a random real in [min, max)
-    min + (max - min) * random_double()
+    min + (max - min) * random_double(rng)
 }
- 
+
+pub fn random_int(rng: &mut Rng, min: usize, max: usize) -> usize {
+    // Return a random integer in [min, max)
+    rng.gen_range(min..max)
+}
+
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     if x < min {
         return min;
@@ -29,4 +51,4 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
         return max;
     }
     x
-}
\ No newline at end of file
+}
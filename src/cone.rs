@@ -0,0 +1,243 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::cylinder::{self, Caps};
+use crate::disk;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+/// A cone or, with two non-zero radii, a cone frustum
+pub struct Cone {
+    base: Point3,
+    axis: Vec3, // Unit vector from the base towards the top
+    height: f64,
+    base_radius: f64,
+    top_radius: f64,
+    caps: Caps,
+    mat: Arc<dyn Material>,
+}
+
+impl Cone {
+    /// Create a cone frustum between two points
+    ///
+    /// # Arguments
+    /// * `base` - The center of the base
+    /// * `top` - The center of the top
+    /// * `base_radius` - The radius at the base
+    /// * `top_radius` - The radius at the top, zero for a pointed cone
+    /// * `caps` - Which ends are closed
+    /// * `mat` - The material of the cone
+    ///
+    /// # Panics
+    /// If `base` and `top` are the same point.
+    pub fn new(
+        base: Point3,
+        top: Point3,
+        base_radius: f64,
+        top_radius: f64,
+        caps: Caps,
+        mat: Arc<dyn Material>,
+    ) -> Cone {
+        assert!((top - base).length_squared() > 0.0, "a cone needs a height");
+        Cone {
+            base,
+            axis: vec3::unit_vector(top - base),
+            height: (top - base).length(),
+            base_radius: base_radius.abs(),
+            top_radius: top_radius.abs(),
+            caps,
+            mat,
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut consider = |t: f64, outward_normal: Vec3| {
+            if t > t_min && t < t_max && closest.is_none_or(|(best, _)| t < best) {
+                closest = Some((t, outward_normal));
+            }
+        };
+
+        // Slanted surface: the distance from the axis grows linearly from
+        // `base_radius` by `slope` per unit of height
+        let slope = (self.top_radius - self.base_radius) / self.height;
+        let oc = r.origin() - self.base;
+        let d_axis = vec3::dot(r.direction(), self.axis);
+        let oc_axis = vec3::dot(oc, self.axis);
+        let d_perp = r.direction() - d_axis * self.axis;
+        let oc_perp = oc - oc_axis * self.axis;
+        let radius_at_origin = self.base_radius + slope * oc_axis;
+        for t in cylinder::solve_quadratic(
+            d_perp.length_squared() - slope * slope * d_axis * d_axis,
+            vec3::dot(oc_perp, d_perp) - slope * radius_at_origin * d_axis,
+            oc_perp.length_squared() - radius_at_origin * radius_at_origin,
+        )
+        .into_iter()
+        .flatten()
+        {
+            let p = r.at(t) - self.base;
+            let h = vec3::dot(p, self.axis);
+            if (0.0..=self.height).contains(&h) {
+                let radial = p - h * self.axis;
+                // At the apex the normal is undefined; point it along the axis
+                let outward_normal = if radial.near_zero() {
+                    self.axis
+                } else {
+                    vec3::unit_vector(vec3::unit_vector(radial) - slope * self.axis)
+                };
+                consider(t, outward_normal);
+            }
+        }
+
+        if self.caps.base && self.base_radius > 0.0 {
+            if let Some(t) =
+                disk::intersect(r, self.base, self.axis, self.base_radius, t_min, t_max)
+            {
+                consider(t, -self.axis);
+            }
+        }
+        if self.caps.top && self.top_radius > 0.0 {
+            let top = self.base + self.height * self.axis;
+            if let Some(t) = disk::intersect(r, top, self.axis, self.top_radius, t_min, t_max) {
+                consider(t, self.axis);
+            }
+        }
+
+        let Some((t, outward_normal)) = closest else {
+            return false;
+        };
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, outward_normal);
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let top = self.base + self.height * self.axis;
+        Some(aabb::surrounding_box(
+            disk::bounding_box(self.base, self.axis, self.base_radius),
+            disk::bounding_box(top, self.axis, self.top_radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::common;
+    use crate::material::Lambertian;
+
+    // Pointed cone of base radius 1 from the origin up to an apex at y = 2
+    fn cone(caps: Caps) -> Cone {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Cone::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+            1.0,
+            0.0,
+            caps,
+            mat,
+        )
+    }
+
+    fn hit(shape: &dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        let r = Ray::new(origin, direction, 0.0);
+        shape
+            .hit(&r, 0.001, common::INFINITY, &mut rec)
+            .then_some(rec)
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn slanted_side_and_base() {
+        let closed = cone(Caps::BOTH);
+        // The side leans in by 1 over a height of 2
+        let slanted = Vec3::new(2.0, 1.0, 0.0) / f64::sqrt(5.0);
+        let rec = hit(
+            &closed,
+            Point3::new(5.0, 1.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert_near(rec.normal, slanted);
+
+        let rec = hit(
+            &closed,
+            Point3::new(0.25, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.p.y() - 1.5).abs() < 1e-9);
+        assert_near(rec.normal, slanted);
+
+        let rec = hit(
+            &closed,
+            Point3::new(0.5, -5.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert!(rec.p.y().abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+
+        // Past the apex, and through the mirror-image cone above it
+        assert!(hit(
+            &closed,
+            Point3::new(5.0, 2.5, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0)
+        )
+        .is_none());
+        assert!(hit(
+            &closed,
+            Point3::new(5.0, 3.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn open_base_shows_the_inside() {
+        let open = cone(Caps::OPEN);
+        let rec = hit(
+            &open,
+            Point3::new(0.25, -5.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.p.y() - 1.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+        assert_near(rec.normal, -Vec3::new(2.0, 1.0, 0.0) / f64::sqrt(5.0));
+    }
+
+    #[test]
+    fn frustum_from_inside() {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let frustum = Cone::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            2.0,
+            1.0,
+            Caps::BOTH,
+            mat,
+        );
+        let inside = Point3::new(0.0, 0.5, 0.0);
+        let rec = hit(&frustum, inside, Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+        let rec = hit(&frustum, inside, Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((rec.t - 0.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+        assert_near(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+    }
+}
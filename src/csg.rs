@@ -0,0 +1,242 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::hittable::{HitRecord, Hittable, Span};
+use crate::ray::Ray;
+use crate::vec3::Point3;
+
+/// How a `Csg` node combines its two solids
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsgOp {
+    // Inside either solid
+    Union,
+    // Inside both solids
+    Intersection,
+    // Inside the first solid but not the second
+    Difference,
+}
+
+impl CsgOp {
+    fn contains(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/// Two solids combined into one by constructive solid geometry
+///
+/// The operands must be closed surfaces (or planes, which act as the
+/// half-space behind their normal). Every point of the result's surface comes
+/// from one of them and keeps that operand's material, so a hole drilled with
+/// a difference is lined with the material of the drill.
+pub struct Csg {
+    op: CsgOp,
+    a: Arc<dyn Hittable>,
+    b: Arc<dyn Hittable>,
+    bbox: Option<Aabb>,
+}
+
+impl Csg {
+    /// Combine two solids
+    ///
+    /// # Arguments
+    /// * `op` - How to combine them
+    /// * `a` - The first solid
+    /// * `b` - The second solid, the one cut away by a difference
+    pub fn new(op: CsgOp, a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Csg {
+        let (box_a, box_b) = (a.bounding_box(), b.bounding_box());
+        let bbox = match op {
+            CsgOp::Union => box_a.zip(box_b).map(|(x, y)| aabb::surrounding_box(x, y)),
+            CsgOp::Intersection => match (box_a, box_b) {
+                (Some(x), Some(y)) => Some(overlap(x, y)),
+                (x, y) => x.or(y),
+            },
+            CsgOp::Difference => box_a,
+        };
+
+        Csg { op, a, b, bbox }
+    }
+
+    /// Everything inside either solid
+    pub fn union(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Csg {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    /// Everything inside both solids
+    pub fn intersection(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Csg {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    /// Solid `a` with everything inside `b` cut away
+    pub fn difference(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Csg {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if let Some(bbox) = self.bbox {
+            if !bbox.hit(r, t_min, t_max) {
+                return false;
+            }
+        }
+
+        for span in self.spans(r, t_min, t_max) {
+            for boundary in [span.enter, span.exit] {
+                if boundary.t.is_finite() && boundary.t >= t_min && boundary.t <= t_max {
+                    *rec = boundary;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    fn spans(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<Span> {
+        // Every boundary of either operand, in order along the ray, and
+        // whether it belongs to `a`
+        let mut boundaries: Vec<(HitRecord, bool)> = Vec::new();
+        for (spans, from_a) in [
+            (self.a.spans(r, t_min, t_max), true),
+            (self.b.spans(r, t_min, t_max), false),
+        ] {
+            for span in spans {
+                boundaries.push((span.enter, from_a));
+                boundaries.push((span.exit, from_a));
+            }
+        }
+        boundaries.sort_by(|x, y| x.0.t.total_cmp(&y.0.t));
+
+        // Each boundary takes the ray into or out of its operand; the ones
+        // that also take it into or out of the result bound the result's spans
+        let mut spans = Vec::new();
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter = None;
+        for (mut rec, from_a) in boundaries {
+            let was_inside = self.op.contains(in_a, in_b);
+            if from_a {
+                in_a = !in_a;
+            } else {
+                in_b = !in_b;
+            }
+            let inside = self.op.contains(in_a, in_b);
+            if inside == was_inside {
+                continue;
+            }
+
+            // The normal already faces the ray; what can change is whether
+            // crossing it enters the result, as where a difference leaves `b`
+            rec.front_face = inside;
+            if inside {
+                enter = Some(rec);
+            } else if let Some(enter) = enter.take() {
+                spans.push(Span { enter, exit: rec });
+            }
+        }
+        spans
+    }
+}
+
+// Box covering the space shared by two boxes
+fn overlap(x: Aabb, y: Aabb) -> Aabb {
+    let mut min = Point3::default();
+    let mut max = Point3::default();
+    for axis in 0..3 {
+        min[axis] = x.min()[axis].max(y.min()[axis]);
+        // Boxes that do not meet give an empty box
+        max[axis] = x.max()[axis].min(y.max()[axis]).max(min[axis]);
+    }
+    Aabb::new(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::common;
+    use crate::cube::Cube;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::{self, Vec3};
+
+    // Outward normal of the result where `rec` was hit
+    fn outward(rec: &HitRecord) -> Vec3 {
+        if rec.front_face {
+            rec.normal
+        } else {
+            -rec.normal
+        }
+    }
+
+    #[test]
+    fn difference_normals_face_outward() {
+        // A unit cube with a ball bitten out of its top face
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let cube = Arc::new(Cube::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            mat.clone(),
+        ));
+        let ball = Arc::new(Sphere::new(Point3::new(0.0, 1.0, 0.0), 0.6, mat));
+        let bitten = Csg::difference(cube, ball);
+        let inside = |p: Point3| {
+            p.x().abs() < 1.0
+                && p.y().abs() < 1.0
+                && p.z().abs() < 1.0
+                && (p - Point3::new(0.0, 1.0, 0.0)).length() > 0.6
+        };
+
+        let rays = [
+            // Straight down into the bite
+            (Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+            (Point3::new(0.3, 5.0, 0.2), Vec3::new(0.0, -1.0, 0.0)),
+            // Sideways through the bite and the cube below it
+            (Point3::new(-5.0, 0.7, 0.1), Vec3::new(1.0, 0.0, 0.0)),
+            (Point3::new(-5.0, 0.2, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+            // Starting in the bite, and inside the remaining solid
+            (Point3::new(0.0, 0.9, 0.0), Vec3::new(0.3, -1.0, 0.2)),
+            (Point3::new(0.0, -0.5, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+        ];
+        for (origin, direction) in rays {
+            let r = Ray::new(origin, direction, 0.0);
+            let spans = bitten.spans(&r, 0.001, common::INFINITY);
+            assert!(!spans.is_empty());
+            for span in spans {
+                for (rec, entering) in [(span.enter, true), (span.exit, false)] {
+                    // A ray starting inside enters at minus infinity
+                    if !rec.t.is_finite() {
+                        continue;
+                    }
+                    assert_eq!(rec.front_face, entering);
+                    // A step along the outward normal leaves the solid
+                    let n = outward(&rec);
+                    assert!((n.length() - 1.0).abs() < 1e-9);
+                    assert!(
+                        !inside(rec.p + 1e-4 * n),
+                        "at {} along {}",
+                        rec.p,
+                        direction
+                    );
+                    assert!(inside(rec.p - 1e-4 * n), "at {} along {}", rec.p, direction);
+                    // Only the side facing the ray is seen
+                    assert!(vec3::dot(rec.normal, direction) < 0.0);
+                }
+            }
+        }
+
+        // The first hit from above is the floor of the bite, facing up
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(bitten.hit(&r, 0.001, common::INFINITY, &mut rec));
+        assert!((rec.p.y() - 0.4).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+    }
+}
@@ -1,5 +1,6 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::ray::Ray;
@@ -8,129 +9,83 @@ use crate::vec3::{Point3, Vec3};
 pub struct Cube {
     min: Point3,
     max: Point3,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
 }
 
 impl Cube {
     /// Create a cube defined by two opposite corners
-    /// 
+    ///
     /// # Arguments
     /// * `min` - The minimum corner (x_min, y_min, z_min)
     /// * `max` - The maximum corner (x_max, y_max, z_max)
     /// * `mat` - The material of the cube
-    pub fn new(min: Point3, max: Point3, mat: Rc<dyn Material>) -> Cube {
+    pub fn new(min: Point3, max: Point3, mat: Arc<dyn Material>) -> Cube {
         Cube { min, max, mat }
     }
 }
 
 impl Hittable for Cube {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let mut t_enter = t_min;
-        let mut t_exit = t_max;
-        let mut normal_axis = 0; // 0 for x, 1 for y, 2 for z
-        let mut entering = true;
+        // Where the ray is inside all three slabs, and the axis of the faces
+        // it crosses at either end
+        let mut t_enter = -f64::INFINITY;
+        let mut t_exit = f64::INFINITY;
+        let mut enter_axis = 0; // 0 for x, 1 for y, 2 for z
+        let mut exit_axis = 0;
 
         let ray_origin = r.origin();
         let ray_dir = r.direction();
 
         // Check intersection with all three slab pairs (x, y, z)
         for axis in 0..3 {
-            let min_val = if axis == 0 {
-                self.min.x()
-            } else if axis == 1 {
-                self.min.y()
-            } else {
-                self.min.z()
-            };
-
-            let max_val = if axis == 0 {
-                self.max.x()
-            } else if axis == 1 {
-                self.max.y()
-            } else {
-                self.max.z()
-            };
-
-            let ray_origin_val = if axis == 0 {
-                ray_origin.x()
-            } else if axis == 1 {
-                ray_origin.y()
-            } else {
-                ray_origin.z()
-            };
-
-            let ray_dir_val = if axis == 0 {
-                ray_dir.x()
-            } else if axis == 1 {
-                ray_dir.y()
-            } else {
-                ray_dir.z()
-            };
-
-            if ray_dir_val.abs() < 1e-8 {
+            if ray_dir[axis].abs() < 1e-8 {
                 // Ray is parallel to slabs, check if origin is within
-                if ray_origin_val < min_val || ray_origin_val > max_val {
+                if ray_origin[axis] < self.min[axis] || ray_origin[axis] > self.max[axis] {
                     return false;
                 }
-            } else {
-                let t1 = (min_val - ray_origin_val) / ray_dir_val;
-                let t2 = (max_val - ray_origin_val) / ray_dir_val;
-
-                let (t_near, t_far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
-
-                if t_near > t_enter {
-                    t_enter = t_near;
-                    normal_axis = axis;
-                    entering = ray_dir_val < 0.0;
-                }
+                continue;
+            }
 
-                if t_far < t_exit {
-                    t_exit = t_far;
-                }
+            let t1 = (self.min[axis] - ray_origin[axis]) / ray_dir[axis];
+            let t2 = (self.max[axis] - ray_origin[axis]) / ray_dir[axis];
+            let (t_near, t_far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
 
-                if t_enter >= t_exit {
-                    return false;
-                }
+            if t_near > t_enter {
+                t_enter = t_near;
+                enter_axis = axis;
+            }
+            if t_far < t_exit {
+                t_exit = t_far;
+                exit_axis = axis;
             }
         }
+        if t_enter > t_exit {
+            return false;
+        }
 
-        if t_enter < t_min || t_enter > t_max {
+        // The face where the ray enters, or where it leaves if it starts inside
+        let (t, axis, sign) = if t_enter >= t_min {
+            (t_enter, enter_axis, -1.0)
+        } else {
+            (t_exit, exit_axis, 1.0)
+        };
+        if t < t_min || t > t_max {
             return false;
         }
 
-        rec.t = t_enter;
+        rec.t = t;
         rec.p = r.at(rec.t);
 
-        // Calculate normal based on which face was hit
-        let normal = match normal_axis {
-            0 => {
-                // X-axis face
-                if entering {
-                    Vec3::new(-1.0, 0.0, 0.0)
-                } else {
-                    Vec3::new(1.0, 0.0, 0.0)
-                }
-            }
-            1 => {
-                // Y-axis face
-                if entering {
-                    Vec3::new(0.0, -1.0, 0.0)
-                } else {
-                    Vec3::new(0.0, 1.0, 0.0)
-                }
-            }
-            _ => {
-                // Z-axis face
-                if entering {
-                    Vec3::new(0.0, 0.0, -1.0)
-                } else {
-                    Vec3::new(0.0, 0.0, 1.0)
-                }
-            }
-        };
+        // The outward normal faces against the ray where it enters
+        let mut normal = Vec3::new(0.0, 0.0, 0.0);
+        normal[axis] = sign * ray_dir[axis].signum();
 
         rec.set_face_normal(r, normal);
         rec.mat = Some(self.mat.clone());
         true
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
 }
@@ -1,30 +1,83 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::aabb::{self, Aabb};
+use crate::disk;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::ray::Ray;
-use crate::vec3::{Point3, Vec3};
+use crate::vec3::{self, Point3, Vec3};
+
+/// Which ends of a cylinder or cone are closed by a flat disk
+#[derive(Clone, Copy)]
+pub struct Caps {
+    pub base: bool,
+    pub top: bool,
+}
+
+impl Caps {
+    pub const BOTH: Caps = Caps {
+        base: true,
+        top: true,
+    };
+    pub const OPEN: Caps = Caps {
+        base: false,
+        top: false,
+    };
+}
 
 pub struct Cylinder {
-    center: Point3,      // Center of the base
-    radius: f64,         // Radius of the cylinder
-    height: f64,         // Height of the cylinder (along Y-axis)
-    mat: Rc<dyn Material>,
+    base: Point3, // Center of the base
+    axis: Vec3,   // Unit vector from the base towards the top
+    height: f64,  // Distance from the base to the top
+    radius: f64,  // Radius of the cylinder
+    caps: Caps,
+    mat: Arc<dyn Material>,
 }
 
 impl Cylinder {
     /// Create a cylinder with base at center and extending upward
-    /// 
+    ///
     /// # Arguments
     /// * `center` - The center of the base of the cylinder
     /// * `radius` - The radius of the cylinder
     /// * `height` - The height of the cylinder (extends along Y-axis)
     /// * `mat` - The material of the cylinder
-    pub fn new(center: Point3, radius: f64, height: f64, mat: Rc<dyn Material>) -> Cylinder {
+    ///
+    /// # Panics
+    /// If `height` is zero.
+    pub fn new(center: Point3, radius: f64, height: f64, mat: Arc<dyn Material>) -> Cylinder {
+        let top = center + Vec3::new(0.0, height.abs(), 0.0);
+        Cylinder::between(center, top, radius, Caps::BOTH, mat)
+    }
+
+    /// Create a cylinder between two points, in any direction
+    ///
+    /// # Arguments
+    /// * `base` - The center of the base
+    /// * `top` - The center of the top
+    /// * `radius` - The radius of the cylinder
+    /// * `caps` - Which ends are closed; an open cylinder is a tube
+    /// * `mat` - The material of the cylinder
+    ///
+    /// # Panics
+    /// If `base` and `top` are the same point.
+    pub fn between(
+        base: Point3,
+        top: Point3,
+        radius: f64,
+        caps: Caps,
+        mat: Arc<dyn Material>,
+    ) -> Cylinder {
+        assert!(
+            (top - base).length_squared() > 0.0,
+            "a cylinder needs a height"
+        );
         Cylinder {
-            center,
+            base,
+            axis: vec3::unit_vector(top - base),
+            height: (top - base).length(),
             radius: radius.abs(),
-            height: height.abs(),
+            caps,
             mat,
         }
     }
@@ -32,81 +85,233 @@ impl Cylinder {
 
 impl Hittable for Cylinder {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let ray_origin = r.origin();
-        let ray_dir = r.direction();
-
-        // Vector from ray origin to cylinder center (at base)
-        let oc = ray_origin - self.center;
-
-        // Cylinder axis is along Y, so we project the ray and cylinder onto the XZ plane
-        let a = ray_dir.x() * ray_dir.x() + ray_dir.z() * ray_dir.z();
-        let b = 2.0 * (oc.x() * ray_dir.x() + oc.z() * ray_dir.z());
-        let c = oc.x() * oc.x() + oc.z() * oc.z() - self.radius * self.radius;
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut consider = |t: f64, outward_normal: Vec3| {
+            if t > t_min && t < t_max && closest.is_none_or(|(best, _)| t < best) {
+                closest = Some((t, outward_normal));
+            }
+        };
 
-        let discriminant = b * b - 4.0 * a * c;
-        if discriminant < 0.0 {
-            return false;
+        // Curved surface: the part of the ray perpendicular to the axis must
+        // be `radius` away from it
+        let oc = r.origin() - self.base;
+        let d_perp = r.direction() - vec3::dot(r.direction(), self.axis) * self.axis;
+        let oc_perp = oc - vec3::dot(oc, self.axis) * self.axis;
+        for t in solve_quadratic(
+            d_perp.length_squared(),
+            vec3::dot(oc_perp, d_perp),
+            oc_perp.length_squared() - self.radius * self.radius,
+        )
+        .into_iter()
+        .flatten()
+        {
+            let p = r.at(t) - self.base;
+            let h = vec3::dot(p, self.axis);
+            if (0.0..=self.height).contains(&h) {
+                consider(t, (p - h * self.axis) / self.radius);
+            }
         }
 
-        let sqrt_d = f64::sqrt(discriminant);
-        let mut t = (-b - sqrt_d) / (2.0 * a);
-
-        // Check if the first intersection is within height bounds
-        let hit_point = ray_origin + t * ray_dir;
-        let height_offset = hit_point.y() - self.center.y();
-
-        if t < t_min || t > t_max || height_offset < 0.0 || height_offset > self.height {
-            // Try the second intersection
-            t = (-b + sqrt_d) / (2.0 * a);
-            let hit_point = ray_origin + t * ray_dir;
-            let height_offset = hit_point.y() - self.center.y();
-
-            if t < t_min || t > t_max || height_offset < 0.0 || height_offset > self.height {
-                // Check bottom cap
-                if ray_dir.y().abs() > 1e-8 {
-                    let t_bottom = (self.center.y() - ray_origin.y()) / ray_dir.y();
-                    if t_bottom >= t_min && t_bottom <= t_max {
-                        let hit_point = ray_origin + t_bottom * ray_dir;
-                        let dist_sq = (hit_point.x() - self.center.x()) * (hit_point.x() - self.center.x())
-                            + (hit_point.z() - self.center.z()) * (hit_point.z() - self.center.z());
-                        if dist_sq <= self.radius * self.radius {
-                            rec.t = t_bottom;
-                            rec.p = hit_point;
-                            rec.set_face_normal(r, Vec3::new(0.0, -1.0, 0.0));
-                            rec.mat = Some(self.mat.clone());
-                            return true;
-                        }
-                    }
-                }
-
-                // Check top cap
-                if ray_dir.y().abs() > 1e-8 {
-                    let t_top = (self.center.y() + self.height - ray_origin.y()) / ray_dir.y();
-                    if t_top >= t_min && t_top <= t_max {
-                        let hit_point = ray_origin + t_top * ray_dir;
-                        let dist_sq = (hit_point.x() - self.center.x()) * (hit_point.x() - self.center.x())
-                            + (hit_point.z() - self.center.z()) * (hit_point.z() - self.center.z());
-                        if dist_sq <= self.radius * self.radius {
-                            rec.t = t_top;
-                            rec.p = hit_point;
-                            rec.set_face_normal(r, Vec3::new(0.0, 1.0, 0.0));
-                            rec.mat = Some(self.mat.clone());
-                            return true;
-                        }
-                    }
-                }
-
-                return false;
+        if self.caps.base {
+            if let Some(t) = disk::intersect(r, self.base, self.axis, self.radius, t_min, t_max) {
+                consider(t, -self.axis);
+            }
+        }
+        if self.caps.top {
+            let top = self.base + self.height * self.axis;
+            if let Some(t) = disk::intersect(r, top, self.axis, self.radius, t_min, t_max) {
+                consider(t, self.axis);
             }
         }
 
+        let Some((t, outward_normal)) = closest else {
+            return false;
+        };
         rec.t = t;
-        rec.p = ray_origin + t * ray_dir;
-
-        // Calculate normal (perpendicular to cylinder axis on the curved surface)
-        let outward_normal = (rec.p - Point3::new(self.center.x(), rec.p.y(), self.center.z())) / self.radius;
+        rec.p = r.at(t);
         rec.set_face_normal(r, outward_normal);
         rec.mat = Some(self.mat.clone());
         true
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let top = self.base + self.height * self.axis;
+        Some(aabb::surrounding_box(
+            disk::bounding_box(self.base, self.axis, self.radius),
+            disk::bounding_box(top, self.axis, self.radius),
+        ))
+    }
+}
+
+/// Real roots of `a t^2 + 2 half_b t + c`, nearest first
+///
+/// A vanishing `a` leaves a linear equation with a single root.
+pub(crate) fn solve_quadratic(a: f64, half_b: f64, c: f64) -> [Option<f64>; 2] {
+    if a.abs() < 1e-12 {
+        if half_b.abs() < 1e-12 {
+            return [None, None];
+        }
+        return [Some(-c / (2.0 * half_b)), None];
+    }
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return [None, None];
+    }
+    let sqrt_d = f64::sqrt(discriminant);
+    let (t0, t1) = ((-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a);
+    [Some(t0.min(t1)), Some(t0.max(t1))]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::common;
+    use crate::material::Lambertian;
+
+    // Cylinder of radius 1 from the origin up to y = 2
+    fn cylinder(caps: Caps) -> Cylinder {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Cylinder::between(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+            1.0,
+            caps,
+            mat,
+        )
+    }
+
+    fn hit(shape: &dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        let r = Ray::new(origin, direction, 0.0);
+        shape
+            .hit(&r, 0.001, common::INFINITY, &mut rec)
+            .then_some(rec)
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn side_and_caps() {
+        let closed = cylinder(Caps::BOTH);
+        let rec = hit(
+            &closed,
+            Point3::new(5.0, 1.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert_near(rec.normal, Vec3::new(1.0, 0.0, 0.0));
+
+        let rec = hit(
+            &closed,
+            Point3::new(0.5, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        let rec = hit(
+            &closed,
+            Point3::new(0.5, -5.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+
+        // Beside, above and past the ends
+        assert!(hit(
+            &closed,
+            Point3::new(5.0, 1.0, 1.5),
+            Vec3::new(-1.0, 0.0, 0.0)
+        )
+        .is_none());
+        assert!(hit(
+            &closed,
+            Point3::new(5.0, 2.5, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn open_ends_show_the_inside() {
+        let tube = cylinder(Caps::OPEN);
+        // Straight down the middle without touching the wall
+        assert!(hit(&tube, Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+
+        // In through the open top to the far wall, seen from inside
+        let rec = hit(
+            &tube,
+            Point3::new(0.0, 5.0, 0.0),
+            Vec3::new(0.25, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.p.y() - 1.0).abs() < 1e-9);
+        assert!(!rec.front_face);
+        assert_near(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+
+        // A base cap alone is seen from inside through the open top
+        let cup = cylinder(Caps {
+            base: true,
+            top: false,
+        });
+        let rec = hit(&cup, Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!(rec.p.y().abs() < 1e-9);
+        assert!(!rec.front_face);
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn from_inside() {
+        let closed = cylinder(Caps::BOTH);
+        let inside = Point3::new(0.0, 1.0, 0.0);
+        let rec = hit(&closed, inside, Vec3::new(0.0, 0.0, 2.0)).unwrap();
+        assert!((rec.t - 0.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+        assert_near(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+
+        let rec = hit(&closed, inside, Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((rec.p.y() - 2.0).abs() < 1e-9);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn tilted_axis() {
+        // Lying along x from 1 to 3, so its top faces +x
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let lying = Cylinder::between(
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(3.0, 0.0, 0.0),
+            0.5,
+            Caps::BOTH,
+            mat,
+        );
+        let rec = hit(
+            &lying,
+            Point3::new(2.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.p.y() - 0.5).abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        let rec = hit(
+            &lying,
+            Point3::new(5.0, 0.1, 0.1),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.p.x() - 3.0).abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(1.0, 0.0, 0.0));
+
+        // Tight apart from the padding that keeps boxes from being flat
+        let bbox = lying.bounding_box().unwrap();
+        assert!((bbox.min() - Point3::new(1.0, -0.5, -0.5)).length() < 1e-3);
+        assert!((bbox.max() - Point3::new(3.0, 0.5, 0.5)).length() < 1e-3);
+    }
 }
@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::common::{self, Rng};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::plane;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+/// A flat circle, for round tabletops, lamp panels and the like
+pub struct Disk {
+    center: Point3,
+    radius: f64,
+    mat: Arc<dyn Material>,
+    // Basis whose `w` axis is the normal; `u` is where the angle of the
+    // texture coordinates starts
+    uvw: Onb,
+}
+
+impl Disk {
+    /// Create a disk
+    ///
+    /// # Arguments
+    /// * `center` - The center of the disk
+    /// * `normal` - The normal vector (perpendicular to the disk)
+    /// * `radius` - The radius of the disk
+    /// * `mat` - The material of the disk
+    ///
+    /// # Panics
+    /// If `normal` is the zero vector.
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: Arc<dyn Material>) -> Disk {
+        assert!(normal.length_squared() > 0.0, "a disk needs a normal");
+        Disk {
+            center,
+            radius: radius.abs(),
+            mat,
+            uvw: Onb::new(normal),
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let Some(t) = intersect(r, self.center, self.uvw.w(), self.radius, t_min, t_max) else {
+            return false;
+        };
+
+        rec.t = t;
+        rec.p = r.at(t);
+        (rec.u, rec.v) = polar_uv(&self.uvw, rec.p - self.center);
+        rec.v /= self.radius;
+        rec.set_face_normal(r, self.uvw.w());
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(bounding_box(self.center, self.uvw.w(), self.radius))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let area = common::PI * self.radius * self.radius;
+        plane::solid_angle_pdf(self, area, origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        // The square root spreads points evenly over the area
+        let distance = self.radius * f64::sqrt(common::random_double(rng));
+        let angle = 2.0 * common::PI * common::random_double(rng);
+        let p = self.center + distance * (angle.cos() * self.uvw.u() + angle.sin() * self.uvw.v());
+        p - origin
+    }
+}
+
+/// Ray parameter where the ray crosses the disk, if that lies within the
+/// acceptable range
+pub fn intersect(
+    r: &Ray,
+    center: Point3,
+    normal: Vec3,
+    radius: f64,
+    t_min: f64,
+    t_max: f64,
+) -> Option<f64> {
+    let t = plane::intersect(r, center, normal, t_min, t_max)?;
+    ((r.at(t) - center).length_squared() <= radius * radius).then_some(t)
+}
+
+/// Box enclosing a disk with the given center, unit normal and radius
+pub fn bounding_box(center: Point3, normal: Vec3, radius: f64) -> Aabb {
+    let extent = Vec3::new(
+        radius * f64::sqrt((1.0 - normal.x() * normal.x()).max(0.0)),
+        radius * f64::sqrt((1.0 - normal.y() * normal.y()).max(0.0)),
+        radius * f64::sqrt((1.0 - normal.z() * normal.z()).max(0.0)),
+    );
+    Aabb::new(center - extent, center + extent)
+}
+
+// Angle around the normal as a fraction of a turn, and distance from the center
+pub(crate) fn polar_uv(uvw: &Onb, offset: Vec3) -> (f64, f64) {
+    let x = vec3::dot(offset, uvw.u());
+    let y = vec3::dot(offset, uvw.v());
+    let angle = f64::atan2(y, x).rem_euclid(2.0 * common::PI);
+    (angle / (2.0 * common::PI), f64::sqrt(x * x + y * y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    // Ceiling light of radius 1 at y = 1, facing down
+    fn disk() -> Disk {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Disk::new(
+            Point3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            1.0,
+            mat,
+        )
+    }
+
+    fn hit_at(shape: &dyn Hittable, x: f64, z: f64) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(x, 0.0, z), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        shape
+            .hit(&r, 0.001, common::INFINITY, &mut rec)
+            .then_some(rec)
+    }
+
+    #[test]
+    fn hit_and_uv() {
+        let disk = disk();
+        let rec = hit_at(&disk, 0.5, 0.0).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-9);
+        // `v` is the distance from the center over the radius
+        assert!((rec.v - 0.5).abs() < 1e-9);
+
+        // `u` goes once around, a quarter turn at a time
+        let quarter = hit_at(&disk, 0.0, 0.5).unwrap();
+        let turn = (quarter.u - rec.u).rem_euclid(1.0);
+        assert!((turn - 0.25).abs() < 1e-9 || (turn - 0.75).abs() < 1e-9);
+        let half = hit_at(&disk, -0.5, 0.0).unwrap();
+        assert!(((half.u - rec.u).rem_euclid(1.0) - 0.5).abs() < 1e-9);
+
+        assert!(hit_at(&disk, 0.8, 0.8).is_none());
+    }
+
+    #[test]
+    fn pdf_matches_sampling() {
+        let disk = disk();
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let up = disk.pdf_value(origin, Vec3::new(0.0, 1.0, 0.0));
+        assert!((up - 1.0 / common::PI).abs() < 1e-9);
+        assert_eq!(disk.pdf_value(origin, Vec3::new(1.0, 0.5, 0.0)), 0.0);
+
+        let mut rng = common::pixel_rng(5, 0);
+        for _ in 0..1000 {
+            assert!(disk.pdf_value(origin, disk.random(origin, &mut rng)) > 0.0);
+        }
+        let n = 200_000;
+        let total: f64 = (0..n)
+            .map(|_| disk.pdf_value(origin, vec3::random_unit_vector(&mut rng)))
+            .sum();
+        let integral = 4.0 * common::PI * total / n as f64;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+    }
+}
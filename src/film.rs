@@ -0,0 +1,66 @@
+use crate::color::Color;
+
+/// Accumulates the linear radiance samples of every pixel
+///
+/// Each pixel keeps the unclamped sum of its sample colors, the number of
+/// samples taken and how many of them hit an object rather than the
+/// background. Pixels are stored row by row starting from the top of the image.
+pub struct Film {
+    width: usize,
+    height: usize,
+    color_sum: Vec<Color>,
+    hits: Vec<u32>,
+    samples: Vec<u32>,
+}
+
+impl Film {
+    /// Create a film with no samples in any pixel
+    pub fn new(width: usize, height: usize) -> Film {
+        Film {
+            width,
+            height,
+            color_sum: vec![Color::default(); width * height],
+            hits: vec![0; width * height],
+            samples: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Add `count` samples to the pixel in column `x` and row `y` (counted from
+    /// the top), whose colors sum to `color_sum` and of which `hits` hit an object
+    pub fn add_samples(&mut self, x: usize, y: usize, color_sum: Color, hits: u32, count: u32) {
+        let i = y * self.width + x;
+        self.color_sum[i] += color_sum;
+        self.hits[i] += hits;
+        self.samples[i] += count;
+    }
+
+    /// Average linear color of a pixel, black if it has no samples
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let i = y * self.width + x;
+        match self.samples[i] {
+            0 => Color::default(),
+            n => self.color_sum[i] / n as f64,
+        }
+    }
+
+    /// Fraction of a pixel's samples that hit an object
+    pub fn alpha(&self, x: usize, y: usize) -> f64 {
+        let i = y * self.width + x;
+        match self.samples[i] {
+            0 => 0.0,
+            n => self.hits[i] as f64 / n as f64,
+        }
+    }
+
+    pub fn samples(&self, x: usize, y: usize) -> u32 {
+        self.samples[y * self.width + x]
+    }
+}
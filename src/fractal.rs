@@ -0,0 +1,340 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::common;
+use crate::material::Material;
+use crate::sdf::{Sdf, SdfShape};
+use crate::vec3::{Point3, Vec3};
+
+/// A fractal drawn through its distance estimator
+///
+/// Distance estimators are rougher than exact distance functions, so fractal
+/// shapes march with more steps than other SDF shapes. Each fractal sits
+/// around the origin at about unit size; place it with an `Instance`.
+pub trait Fractal: Sdf {
+    /// Box enclosing the whole fractal
+    fn bounds(&self) -> Aabb;
+}
+
+/// Make a fractal into a shape that can be added to the world
+pub fn shape(fractal: impl Fractal + 'static, mat: Arc<dyn Material>) -> SdfShape {
+    let bounds = fractal.bounds();
+    SdfShape::new(Arc::new(fractal), bounds, mat).with_limits(1e-4, 1024)
+}
+
+// Box from -half to half on every axis
+fn cube_bounds(half: f64) -> Aabb {
+    Aabb::new(
+        Point3::new(-half, -half, -half),
+        Point3::new(half, half, half),
+    )
+}
+
+/// The Mandelbulb: the Mandelbrot set carried into 3D with spherical
+/// coordinates
+pub struct Mandelbulb {
+    power: f64,
+    iterations: usize,
+    bailout: f64,
+}
+
+impl Mandelbulb {
+    /// Create a Mandelbulb
+    ///
+    /// # Arguments
+    /// * `power` - The exponent of the iteration, 2 or more; 8 gives the
+    ///   classic bulb
+    /// * `iterations` - How many times to iterate; more gives finer detail
+    /// * `bailout` - The radius beyond which a point is taken to escape
+    pub fn new(power: f64, iterations: usize, bailout: f64) -> Mandelbulb {
+        Mandelbulb {
+            power: power.max(2.0),
+            iterations,
+            bailout,
+        }
+    }
+
+    // Distance estimate and orbit trap (closest approach to the origin)
+    fn orbit(&self, p: Point3) -> (f64, f64) {
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        let mut trap = r;
+        for _ in 0..self.iterations {
+            if r > self.bailout || r == 0.0 {
+                break;
+            }
+            let theta = f64::acos(z.z() / r) * self.power;
+            let phi = f64::atan2(z.y(), z.x()) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            z = r.powf(self.power)
+                * Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+                + p;
+            r = z.length();
+            trap = trap.min(r);
+        }
+        // An orbit stuck at the origin is in the set, where `ln` would give NaN
+        if r == 0.0 {
+            return (0.0, trap);
+        }
+        (0.5 * r.ln() * r / dr, trap)
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Point3) -> f64 {
+        self.orbit(p).0
+    }
+
+    fn trap(&self, p: Point3) -> f64 {
+        common::clamp(self.orbit(p).1, 0.0, 1.0)
+    }
+}
+
+impl Fractal for Mandelbulb {
+    fn bounds(&self) -> Aabb {
+        // Points farther out than this escape on the first iteration
+        cube_bounds(1.01 * 2.0f64.powf(1.0 / (self.power - 1.0)))
+    }
+}
+
+/// The Menger sponge: a cube from -1 to 1 with the middle of every face
+/// tunnelled through, repeated on ever smaller cubes
+pub struct MengerSponge {
+    iterations: usize,
+}
+
+impl MengerSponge {
+    /// Create a Menger sponge with the given number of levels of holes
+    pub fn new(iterations: usize) -> MengerSponge {
+        MengerSponge { iterations }
+    }
+
+    // Distance and how deep a level of holes the nearest surface belongs to,
+    // as a fraction of all levels
+    fn orbit(&self, p: Point3) -> (f64, f64) {
+        let q = Vec3::new(p.x().abs() - 1.0, p.y().abs() - 1.0, p.z().abs() - 1.0);
+        let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0));
+        let mut d = outside.length() + q.x().max(q.y()).max(q.z()).min(0.0);
+        let mut trap = 0.0;
+
+        let mut scale = 1.0;
+        for level in 0..self.iterations {
+            // Position within the cell at this level, from -1 to 1
+            let mut a = Vec3::default();
+            for axis in 0..3 {
+                a[axis] = (p[axis] * scale).rem_euclid(2.0) - 1.0;
+            }
+            scale *= 3.0;
+            let r = Vec3::new(
+                (1.0 - 3.0 * a.x().abs()).abs(),
+                (1.0 - 3.0 * a.y().abs()).abs(),
+                (1.0 - 3.0 * a.z().abs()).abs(),
+            );
+            // Distance to the cross of tunnels through this cell
+            let cross = r.x().max(r.y()).min(r.y().max(r.z())).min(r.z().max(r.x()));
+            let c = (cross - 1.0) / scale;
+            if c > d {
+                d = c;
+                trap = (level + 1) as f64 / self.iterations as f64;
+            }
+        }
+        (d, trap)
+    }
+}
+
+impl Sdf for MengerSponge {
+    fn distance(&self, p: Point3) -> f64 {
+        self.orbit(p).0
+    }
+
+    fn trap(&self, p: Point3) -> f64 {
+        self.orbit(p).1
+    }
+}
+
+impl Fractal for MengerSponge {
+    fn bounds(&self) -> Aabb {
+        cube_bounds(1.0)
+    }
+}
+
+/// A quaternion Julia set, z ← z² + c, sliced through the space where the
+/// last component of z is zero
+pub struct QuaternionJulia {
+    c: [f64; 4],
+    iterations: usize,
+    bailout: f64,
+}
+
+impl QuaternionJulia {
+    /// Create a quaternion Julia set
+    ///
+    /// # Arguments
+    /// * `c` - The constant added on every iteration, which picks the shape
+    /// * `iterations` - How many times to iterate; more gives finer detail
+    /// * `bailout` - The radius beyond which a point is taken to escape
+    pub fn new(c: [f64; 4], iterations: usize, bailout: f64) -> QuaternionJulia {
+        QuaternionJulia {
+            c,
+            iterations,
+            bailout,
+        }
+    }
+
+    // Distance estimate and orbit trap (closest approach to the origin)
+    fn orbit(&self, p: Point3) -> (f64, f64) {
+        let mut z = [p.x(), p.y(), p.z(), 0.0];
+        // Derivative of z with respect to the starting point
+        let mut dz = [1.0, 0.0, 0.0, 0.0];
+        let mut m2 = dot(z, z);
+        let mut trap = m2;
+        for _ in 0..self.iterations {
+            let zdz = multiply(z, dz);
+            let zz = multiply(z, z);
+            for i in 0..4 {
+                dz[i] = 2.0 * zdz[i];
+                z[i] = zz[i] + self.c[i];
+            }
+            m2 = dot(z, z);
+            trap = trap.min(m2);
+            if m2 > self.bailout * self.bailout {
+                break;
+            }
+        }
+        let m = m2.sqrt();
+        (0.5 * m * m.ln() / dot(dz, dz).sqrt(), trap.sqrt())
+    }
+}
+
+impl Sdf for QuaternionJulia {
+    fn distance(&self, p: Point3) -> f64 {
+        self.orbit(p).0
+    }
+
+    fn trap(&self, p: Point3) -> f64 {
+        common::clamp(self.orbit(p).1, 0.0, 1.0)
+    }
+}
+
+impl Fractal for QuaternionJulia {
+    fn bounds(&self) -> Aabb {
+        // Beyond this radius |z|² grows faster than |c| can pull it back
+        let c = dot(self.c, self.c).sqrt();
+        cube_bounds(1.01 * 0.5 * (1.0 + f64::sqrt(1.0 + 4.0 * c)))
+    }
+}
+
+fn dot(a: [f64; 4], b: [f64; 4]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+}
+
+// Hamilton product of two quaternions, real part first
+fn multiply(a: [f64; 4], b: [f64; 4]) -> [f64; 4] {
+    [
+        a[0] * b[0] - a[1] * b[1] - a[2] * b[2] - a[3] * b[3],
+        a[0] * b[1] + a[1] * b[0] + a[2] * b[3] - a[3] * b[2],
+        a[0] * b[2] - a[1] * b[3] + a[2] * b[0] + a[3] * b[1],
+        a[0] * b[3] + a[1] * b[2] - a[2] * b[1] + a[3] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mandelbulb_distance_at_origin() {
+        let bulb = Mandelbulb::new(8.0, 8, 2.0);
+        assert_eq!(bulb.distance(Point3::new(0.0, 0.0, 0.0)), 0.0);
+        assert!(bulb.distance(Point3::new(0.0, 0.0, 2.0)) > 0.0);
+    }
+
+    // Points on the surface of the cube from -half to half, 5 to a side
+    fn cube_surface(half: f64) -> Vec<Point3> {
+        let steps = [-1.0, -0.5, 0.0, 0.5, 1.0];
+        let mut points = Vec::new();
+        for x in steps {
+            for y in steps {
+                for z in steps {
+                    if [x, y, z].iter().any(|c: &f64| c.abs() == 1.0) {
+                        points.push(Point3::new(half * x, half * y, half * z));
+                    }
+                }
+            }
+        }
+        points
+    }
+
+    fn assert_outside_bounds_positive(fractal: &dyn Fractal) {
+        let half = fractal.bounds().max().x();
+        for scale in [1.01, 1.5, 4.0] {
+            for p in cube_surface(scale * half) {
+                let d = fractal.distance(p);
+                assert!(d > 0.0, "distance {} at {}", d, p);
+            }
+        }
+    }
+
+    #[test]
+    fn menger_sponge_tunnels_and_solid() {
+        for iterations in 1..=4 {
+            let sponge = MengerSponge::new(iterations);
+            // The middle of the cube is hollowed out by the first tunnels,
+            // whose walls are a third away
+            let d = sponge.distance(Point3::new(0.0, 0.0, 0.0));
+            assert!((d - 1.0 / 3.0).abs() < 1e-12);
+            for p in [
+                Point3::new(0.0, 0.1, 0.9),
+                Point3::new(-0.9, 0.0, 0.2),
+                Point3::new(0.2, -0.95, -0.1),
+            ] {
+                assert!(sponge.distance(p) > 0.0, "{} in a tunnel", p);
+            }
+
+            // Near a corner, solid at every level, just inside the faces
+            let d = sponge.distance(Point3::new(0.99, 0.99, -0.99));
+            assert!((-0.01 - 1e-12..0.0).contains(&d));
+
+            assert_outside_bounds_positive(&sponge);
+        }
+
+        // In an edge cube, solid until the second level of holes
+        let p = Point3::new(2.0 / 3.0, 2.0 / 3.0, 0.5);
+        assert!(MengerSponge::new(1).distance(p) < 0.0);
+        assert!(MengerSponge::new(2).distance(p) > 0.0);
+        assert!(MengerSponge::new(2).trap(p) == 1.0);
+    }
+
+    #[test]
+    fn quaternion_julia_inside_and_bounds() {
+        // For a real c the fixed point (1 - sqrt(1 - 4c)) / 2 attracts the
+        // points around it, which are inside the set
+        let julia = QuaternionJulia::new([-0.2, 0.0, 0.0, 0.0], 12, 4.0);
+        let fixed = (1.0 - f64::sqrt(1.8)) / 2.0;
+        for p in [
+            Point3::new(fixed, 0.0, 0.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.1, 0.2, -0.1),
+        ] {
+            assert!(julia.distance(p) < 0.0, "{} should be inside", p);
+        }
+        assert!(julia.distance(Point3::new(1.5, 0.0, 0.0)) > 0.0);
+
+        for c in [
+            [-0.2, 0.0, 0.0, 0.0],
+            [-0.2, 0.8, 0.0, 0.0],
+            [-0.45, 0.447, 0.181, 0.306],
+            [0.5, -0.5, 0.5, 0.3],
+        ] {
+            let julia = QuaternionJulia::new(c, 10, 4.0);
+            assert_outside_bounds_positive(&julia);
+            let trap = julia.trap(Point3::new(0.3, 0.1, 0.0));
+            assert!((0.0..=1.0).contains(&trap));
+        }
+    }
+}
@@ -1,23 +1,31 @@
-use std::rc::Rc;
- 
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::common::{self, Rng};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::{self, Point3, Vec3};
- 
+
 #[derive(Clone, Default)]
 pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
-    pub mat: Option<Rc<dyn Material>>,
+    pub mat: Option<Arc<dyn Material>>,
     pub t: f64,
+    // Surface coordinates of the hit point, for shapes that define them
+    pub u: f64,
+    pub v: f64,
+    // Orbit trap of a fractal at the hit point, from 0 to 1, for materials
+    // that color by it; zero for other shapes
+    pub trap: f64,
     pub front_face: bool,
 }
- 
+
 impl HitRecord {
     pub fn new() -> HitRecord {
         Default::default()
     }
- 
+
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         self.front_face = vec3::dot(r.direction(), outward_normal) < 0.0;
         self.normal = if self.front_face {
@@ -27,7 +35,111 @@ impl HitRecord {
         };
     }
 }
- 
-pub trait Hittable {
+
+/// A stretch of a ray that lies inside a solid, from the point where the ray
+/// enters the solid to the point where it leaves
+///
+/// A ray that starts inside has an `enter` record at minus infinity, and one
+/// still inside when the search ends has an `exit` at infinity; neither of
+/// those records has a material.
+#[derive(Clone)]
+pub struct Span {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
+}
+
+impl Span {
+    /// The whole ray, for a ray that never leaves the solid
+    pub fn everywhere() -> Span {
+        Span {
+            enter: open_end(-common::INFINITY),
+            exit: open_end(common::INFINITY),
+        }
+    }
+}
+
+// Boundary of a span that lies beyond the part of the ray searched
+fn open_end(t: f64) -> HitRecord {
+    HitRecord {
+        t,
+        ..HitRecord::new()
+    }
+}
+
+// How far past one crossing the search for the next one starts
+const SPAN_EPSILON: f64 = 1e-6;
+
+pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
-}
\ No newline at end of file
+
+    /// Like `hit`, with the renderer's random number generator for objects
+    /// that rays hit at a random point, such as participating media
+    ///
+    /// Those objects are only seen through this method. Objects that hold
+    /// others pass `rng` on to them; the rest need not override it.
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        _rng: &mut Rng,
+    ) -> bool {
+        self.hit(ray, t_min, t_max, rec)
+    }
+
+    /// Box enclosing the object, or `None` if the object is unbounded
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Density, per unit solid angle, with which `random` picks `direction`
+    /// from `origin`
+    ///
+    /// Objects that cannot be sampled as lights return zero.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// A random direction from `origin` towards a point on the object
+    fn random(&self, _origin: Point3, _rng: &mut Rng) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    /// Every span of the ray between `t_min` and `t_max` that lies inside the
+    /// object, in order along the ray
+    ///
+    /// The default finds the crossings one `hit` at a time and takes those
+    /// where the surface faces the ray as entries, which suits any closed
+    /// surface with outward normals.
+    fn spans(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<Span> {
+        let mut spans = Vec::new();
+        let mut enter: Option<HitRecord> = None;
+        let mut first = true;
+        let mut rec = HitRecord::new();
+        let mut t = t_min;
+        while self.hit(r, t, t_max, &mut rec) {
+            t = rec.t + SPAN_EPSILON;
+            if rec.front_face {
+                // Overlapping parts of the surface may enter twice in a row
+                enter.get_or_insert_with(|| rec.clone());
+            } else if let Some(enter) = enter.take() {
+                spans.push(Span {
+                    enter,
+                    exit: rec.clone(),
+                });
+            } else if first {
+                spans.push(Span {
+                    enter: open_end(-common::INFINITY),
+                    exit: rec.clone(),
+                });
+            }
+            first = false;
+        }
+        if let Some(enter) = enter {
+            spans.push(Span {
+                enter,
+                exit: open_end(common::INFINITY),
+            });
+        }
+        spans
+    }
+}
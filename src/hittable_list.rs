@@ -1,8 +1,14 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::common::{self, Rng};
 use crate::hittable::{HitRecord, Hittable};
 use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
 
+#[derive(Default)]
 pub struct HittableList {
-    objects: Vec<Box<dyn Hittable>>,
+    objects: Vec<Arc<dyn Hittable>>,
 }
 
 impl HittableList {
@@ -12,29 +18,126 @@ impl HittableList {
         }
     }
 
-    pub fn add(&mut self, object: Box<dyn Hittable>) {
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
         self.objects.push(object);
     }
 
     pub fn clear(&mut self) {
         self.objects.clear();
     }
-}
 
-impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord::new();
+    pub fn objects(&self) -> &[Arc<dyn Hittable>] {
+        &self.objects
+    }
+
+    // Closest hit of any object, each tested by `hit_object` against the
+    // closest distance found so far
+    fn closest_hit(
+        &self,
+        t_max: f64,
+        rec: &mut HitRecord,
+        mut hit_object: impl FnMut(&dyn Hittable, f64, &mut HitRecord) -> bool,
+    ) -> bool {
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
 
         for object in &self.objects {
-            if object.hit(ray, t_min, closest_so_far, &mut temp_rec) {
+            // A fresh record for each object, so that fields one shape does not
+            // set, such as `trap`, cannot carry over from another
+            let mut temp_rec = HitRecord::new();
+            if hit_object(&**object, closest_so_far, &mut temp_rec) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
-                *rec = temp_rec.clone();
+                *rec = temp_rec;
             }
         }
 
         hit_anything
     }
 }
+
+impl Hittable for HittableList {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.closest_hit(t_max, rec, |object, t_max, rec| {
+            object.hit(ray, t_min, t_max, rec)
+        })
+    }
+
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut Rng,
+    ) -> bool {
+        self.closest_hit(t_max, rec, |object, t_max, rec| {
+            object.sample_hit(ray, t_min, t_max, rec, rng)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut output_box: Option<Aabb> = None;
+        for object in &self.objects {
+            let object_box = object.bounding_box()?;
+            output_box = Some(match output_box {
+                Some(b) => aabb::surrounding_box(b, object_box),
+                None => object_box,
+            });
+        }
+        output_box
+    }
+
+    // Sampling picks one of the objects uniformly, so the density is the
+    // average of theirs
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let index = common::random_int(rng, 0, self.objects.len());
+        self.objects[index].random(origin, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::fractal::{self, Mandelbulb};
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    #[test]
+    fn trap_does_not_leak_between_objects() {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut world = HittableList::new();
+        // The fractal is tried first and hit, then the sphere in front of it
+        world.add(Arc::new(fractal::shape(
+            Mandelbulb::new(8.0, 8, 2.0),
+            mat.clone(),
+        )));
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, 2.5), 0.5, mat)));
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(world.objects()[0].hit(&r, 0.001, common::INFINITY, &mut rec));
+        assert!(rec.trap > 0.0);
+
+        let mut rec = HitRecord::new();
+        assert!(world.hit(&r, 0.001, common::INFINITY, &mut rec));
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert_eq!(rec.trap, 0.0);
+    }
+}
@@ -0,0 +1,37 @@
+pub mod aabb;
+pub mod animation;
+pub mod annulus;
+pub mod bvh;
+pub mod camera;
+pub mod capsule;
+pub mod color;
+pub mod common;
+pub mod cone;
+pub mod csg;
+pub mod cube;
+pub mod cylinder;
+pub mod disk;
+pub mod film;
+pub mod fractal;
+pub mod hittable;
+pub mod hittable_list;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod obj;
+pub mod onb;
+pub mod output;
+pub mod plane;
+pub mod png;
+pub mod quad;
+pub mod ray;
+pub mod render;
+pub mod scene;
+pub mod sdf;
+pub mod sphere;
+pub mod stereo;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
+pub mod vec3;
+pub mod voxel;
@@ -1,124 +1,159 @@
-mod camera;
-mod color;
-mod common;
-mod cube;
-mod cylinder;
-mod hittable;
-mod hittable_list;
-mod material;
-mod plane;
-mod ray;
-mod sphere;
-mod vec3;
- 
-use std::io;
-use std::rc::Rc;
+mod cli;
 
-use camera::Camera;
-use color::Color;
-use cube::Cube;
-use cylinder::Cylinder;
-use hittable::{HitRecord, Hittable};
-use hittable_list::HittableList;
-use material::Lambertian;
-use plane::Plane;
-use ray::Ray;
-use sphere::Sphere;
-use vec3::{Point3, Vec3};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process;
+use std::time::Instant;
 
-fn ray_color(r: &Ray, world: &dyn Hittable, depth: i32) -> Color {
-    // If we've exceeded the ray bounce limit, no more light is gathered
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
- 
-    let mut rec = HitRecord::new();
-    if world.hit(r, 0.001, common::INFINITY, &mut rec) {
-        let mut attenuation = Color::default();
-        let mut scattered = Ray::default();
-        if rec
-            .mat
-            .as_ref()
-            .unwrap()
-            .scatter(r, &rec, &mut attenuation, &mut scattered)
-        {
-            return attenuation * ray_color(&scattered, world, depth - 1);
-        }
-        return Color::new(0.0, 0.0, 0.0);
-    }
- 
-    let unit_direction = vec3::unit_vector(r.direction());
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
-}
- 
-fn main() {
-    // Image dimensions
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-    const IMAGE_WIDTH: i32 = 400;
-    // const IMAGE_HEIGHT: i32 = 200;
-    const IMAGE_HEIGHT: i32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as i32;
-    const SAMPLES_PER_PIXEL: i32 = 50;  // Lower samples for darker appearance
-    const MAX_DEPTH: i32 = 30;           // Lower depth for darker scene
+use ray_tracing::bvh::BvhNode;
+use ray_tracing::film::Film;
+use ray_tracing::render;
+use ray_tracing::scene;
 
-    // World
+use cli::{Args, Verbosity};
 
-    let mut world = HittableList::new();
+fn main() {
+    let args = Args::parse(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("error: {}\n\n{}", err, cli::USAGE);
+        process::exit(2);
+    });
+    if args.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
 
-    // Flat plane - dark gray matte surface
-    let plane_material = Rc::new(Lambertian::new(Color::new(0.3, 0.3, 0.3)));
-    world.add(Box::new(Plane::horizontal(0.0, plane_material)));
+    // Scene: from the given file, or the built-in default scene
+    let loaded = match &args.scene {
+        Some(path) => scene::load(path),
+        None => scene::parse(scene::DEFAULT_SCENE),
+    };
+    let scene = loaded.unwrap_or_else(|err| {
+        eprintln!(
+            "{}: {}",
+            args.scene.as_deref().unwrap_or("default scene"),
+            err
+        );
+        process::exit(1);
+    });
+    if args.frames.is_some() && scene.animation.is_none() {
+        eprintln!(
+            "{}: --frames needs a scene with an [animation] table",
+            args.scene.as_deref().unwrap_or("default scene")
+        );
+        process::exit(1);
+    }
 
-    // Sphere - dark blue color (left)
-    let sphere_material = Rc::new(Lambertian::new(Color::new(0.2, 0.2, 0.4)));
-    world.add(Box::new(Sphere::new(
-        Point3::new(-3.0, 1.0, 0.0),
-        1.0,
-        sphere_material,
-    )));
+    // Command line options take precedence over the scene's render settings
+    let mut settings = scene.settings;
+    (settings.image_width, settings.image_height) =
+        args.image_size(settings.image_width, settings.image_height);
+    if let Some(samples) = args.samples_per_pixel {
+        settings.samples_per_pixel = samples;
+    }
+    if let Some(depth) = args.max_depth {
+        settings.max_depth = depth;
+    }
+    if let Some(threads) = args.threads {
+        settings.threads = threads;
+    }
+    if let Some(seed) = args.seed {
+        settings.seed = seed;
+    }
+    settings.progress = args.verbosity >= Verbosity::Normal;
 
-    // Cube - dark purple color (center-left)
-    let cube_material = Rc::new(Lambertian::new(Color::new(0.3, 0.2, 0.4)));
-    world.add(Box::new(Cube::new(
-        Point3::new(-0.75, 0.0, -0.75),
-        Point3::new(0.75, 1.5, 0.75),
-        cube_material,
-    )));
+    let aspect_ratio = settings.image_width as f64 / settings.image_height as f64;
+    let world = BvhNode::new(scene.world);
 
-    // Cylinder - dark red color (center-right)
-    let cylinder_material = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.2)));
-    world.add(Box::new(Cylinder::new(
-        Point3::new(2.0, 0.0, 0.0),
-        0.8,
-        1.6,
-        cylinder_material,
-    )));
+    if args.verbosity == Verbosity::Verbose {
+        eprintln!(
+            "Rendering {}x{}, {} samples per pixel, max depth {}, {} threads, seed {}",
+            settings.image_width,
+            settings.image_height,
+            settings.samples_per_pixel,
+            settings.max_depth,
+            settings.threads,
+            settings.seed,
+        );
+    }
+
+    // Camera on a frame of the animation, if the scene has one
+    let camera_on = |frame: f64| match &scene.animation {
+        Some(animation) => animation.apply(&scene.camera, frame),
+        None => scene.camera,
+    };
 
-    // Camera positioned to see all objects from a different angle
+    // Render
 
-    let cam = Camera::new(
-        Point3::new(5.0, 3.0, 3.0),
-        Point3::new(0.0, 0.8, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        35.0,
-        ASPECT_RATIO,
-    );    // Render
- 
-    print!("P3\n{} {}\n255\n", IMAGE_WIDTH, IMAGE_HEIGHT);
- 
-    for j in (0..IMAGE_HEIGHT).rev() {
-        eprint!("\rScanlines remaining: {} ", j);
-        for i in 0..IMAGE_WIDTH {
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _ in 0..SAMPLES_PER_PIXEL {
-                let u = (i as f64 + common::random_double()) / (IMAGE_WIDTH - 1) as f64;
-                let v = (j as f64 + common::random_double()) / (IMAGE_HEIGHT - 1) as f64;
-                let r = cam.get_ray(u, v);
-                pixel_color += ray_color(&r, &world, MAX_DEPTH);
+    let start = Instant::now();
+    match args.frames {
+        Some((first, last)) => {
+            let dir = Path::new(args.output.as_deref().unwrap_or("."));
+            if let Err(err) = fs::create_dir_all(dir) {
+                eprintln!("cannot create {}: {}", dir.display(), err);
+                process::exit(1);
+            }
+            let seed = settings.seed;
+            for frame in first..=last {
+                // Each frame gets its own noise, still reproducible from the seed
+                settings.seed =
+                    seed.wrapping_add((frame as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+                if args.verbosity >= Verbosity::Normal {
+                    eprint!(
+                        "Frame {} ({} of {})",
+                        frame,
+                        frame - first + 1,
+                        last - first + 1
+                    );
+                    if args.verbosity == Verbosity::Verbose {
+                        eprint!(", seed {}", settings.seed);
+                    }
+                    eprintln!();
+                }
+                let cam = camera_on(frame as f64).build(aspect_ratio);
+                let film = render::render(&world, &scene.lights, cam.as_ref(), &settings);
+                let path = dir.join(format!("frame_{:04}.{}", frame, args.format.extension()));
+                write_image(&args, Some(&path), &film);
+                if args.verbosity >= Verbosity::Normal {
+                    eprintln!();
+                }
+            }
+        }
+        None => {
+            // A still of an animated scene shows its first keyframe
+            let frame = scene.animation.as_ref().map_or(1.0, |a| a.frame_range().0);
+            let cam = camera_on(frame).build(aspect_ratio);
+            let film = render::render(&world, &scene.lights, cam.as_ref(), &settings);
+            write_image(&args, args.output.as_deref().map(Path::new), &film);
+            if args.verbosity >= Verbosity::Normal {
+                eprintln!();
             }
-            color::write_color(&mut io::stdout(), pixel_color, SAMPLES_PER_PIXEL);
         }
     }
- 
-    eprint!("\nDone.\n");
-}
\ No newline at end of file
+    let elapsed = start.elapsed();
+
+    if args.verbosity == Verbosity::Verbose {
+        eprintln!("Rendered in {:.2?}", elapsed);
+    } else if args.verbosity == Verbosity::Normal {
+        eprintln!("Done.");
+    }
+}
+
+// Write the image to `path`, or to stdout if there is none
+fn write_image(args: &Args, path: Option<&Path>, film: &Film) {
+    let destination: Box<dyn Write> = match path {
+        Some(path) => Box::new(File::create(path).unwrap_or_else(|err| {
+            eprintln!("\ncannot create {}: {}", path.display(), err);
+            process::exit(1);
+        })),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut out = BufWriter::new(destination);
+    let writer = args.format.writer(args.bit_depth, args.alpha);
+    let written = writer.write(&mut out, film).and_then(|_| out.flush());
+    if let Err(err) = written {
+        eprintln!("\ncannot write image: {}", err);
+        process::exit(1);
+    }
+}
@@ -1,54 +1,85 @@
 use crate::color::Color;
+use crate::common::{self, Rng};
 use crate::hittable::HitRecord;
+use crate::onb::Onb;
 use crate::ray::Ray;
-use crate::{common, vec3};
- 
-pub trait Material {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool;
-}
- 
+use crate::vec3::{self, Vec3};
+
+/// Outcome of sampling a material at a hit point
+pub struct ScatterRecord {
+    // Throughput of the sampled direction: `eval / pdf`, or the reflectance
+    // of a specular surface
+    pub attenuation: Color,
+    pub scattered: Ray,
+    // Density of the sampled direction per unit solid angle; meaningless for
+    // specular surfaces, whose lobes are delta distributions
+    pub pdf: f64,
+    pub is_specular: bool,
+}
+
+pub trait Material: Send + Sync {
+    /// Sample a scattered ray, or `None` if the ray is absorbed
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _rng: &mut Rng) -> Option<ScatterRecord> {
+        None
+    }
+
+    /// BSDF times the cosine of the outgoing direction, for light leaving
+    /// along `scattered`; zero for specular surfaces
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Density with which `scatter` picks the direction of `scattered`
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    /// Light given off by the surface at the hit point, black for most materials
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+}
+
 pub struct Lambertian {
     albedo: Color,
 }
- 
+
 impl Lambertian {
     pub fn new(a: Color) -> Lambertian {
         Lambertian { albedo: a }
     }
 }
- 
+
 impl Material for Lambertian {
-    fn scatter(
-        &self,
-        _r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = rec.normal + vec3::random_unit_vector();
- 
-        // Catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
- 
-        *attenuation = self.albedo;
-        *scattered = Ray::new(rec.p, scatter_direction);
-        true
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        // Cosine-weighted sampling cancels the cosine in the BSDF, leaving the albedo
+        let uvw = Onb::new(rec.normal);
+        let scatter_direction = uvw.local(vec3::random_cosine_direction(rng));
+        let scattered = Ray::new(rec.p, scatter_direction, r_in.time());
+
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            pdf: self.scattering_pdf(r_in, rec, &scattered),
+            scattered,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.albedo * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cosine = vec3::dot(rec.normal, vec3::unit_vector(scattered.direction()));
+        f64::max(cosine, 0.0) / common::PI
     }
 }
- 
+
 pub struct Metal {
     albedo: Color,
     fuzz: f64,
 }
- 
+
 impl Metal {
     pub fn new(a: Color, f: f64) -> Metal {
         Metal {
@@ -57,34 +88,41 @@ impl Metal {
         }
     }
 }
- 
+
 impl Material for Metal {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
+    // Fuzzy reflections are still treated as specular: their lobe has no
+    // density the renderer could weigh against light sampling
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
         let reflected = vec3::reflect(vec3::unit_vector(r_in.direction()), rec.normal);
- 
-        *attenuation = self.albedo;
-        *scattered = Ray::new(rec.p, reflected + self.fuzz * vec3::random_in_unit_sphere());
-        vec3::dot(scattered.direction(), rec.normal) > 0.0
+        let scattered = Ray::new(
+            rec.p,
+            reflected + self.fuzz * vec3::random_in_unit_sphere(rng),
+            r_in.time(),
+        );
+        if vec3::dot(scattered.direction(), rec.normal) <= 0.0 {
+            return None;
+        }
+
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            scattered,
+            pdf: 0.0,
+            is_specular: true,
+        })
     }
 }
- 
+
 pub struct Dielectric {
     ir: f64, // Index of refraction
 }
- 
+
 impl Dielectric {
     pub fn new(index_of_refraction: f64) -> Dielectric {
         Dielectric {
             ir: index_of_refraction,
         }
     }
- 
+
     fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         // Use Schlick's approximation for reflectance
         let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
@@ -92,36 +130,177 @@ impl Dielectric {
         r0 + (1.0 - r0) * f64::powf(1.0 - cosine, 5.0)
     }
 }
- 
+
 impl Material for Dielectric {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
         let refraction_ratio = if rec.front_face {
             1.0 / self.ir
         } else {
             self.ir
         };
- 
+
         let unit_direction = vec3::unit_vector(r_in.direction());
         let cos_theta = f64::min(vec3::dot(-unit_direction, rec.normal), 1.0);
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
- 
+
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let direction = if cannot_refract
-            || Self::reflectance(cos_theta, refraction_ratio) > common::random_double()
+            || Self::reflectance(cos_theta, refraction_ratio) > common::random_double(rng)
         {
             vec3::reflect(unit_direction, rec.normal)
         } else {
             vec3::refract(unit_direction, rec.normal, refraction_ratio)
         };
- 
-        *attenuation = Color::new(1.0, 1.0, 1.0);
-        *scattered = Ray::new(rec.p, direction);
-        true
+
+        Some(ScatterRecord {
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            scattered: Ray::new(rec.p, direction, r_in.time()),
+            pdf: 0.0,
+            is_specular: true,
+        })
+    }
+}
+
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    /// Create a light source that emits `emit` evenly in all directions
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        self.emit
+    }
+}
+
+/// Diffuse surface colored by the orbit trap of a fractal, blending from `low`
+/// where the trap is 0 to `high` where it is 1
+pub struct OrbitTrap {
+    low: Color,
+    high: Color,
+}
+
+impl OrbitTrap {
+    pub fn new(low: Color, high: Color) -> OrbitTrap {
+        OrbitTrap { low, high }
+    }
+
+    fn surface(&self, rec: &HitRecord) -> Lambertian {
+        let t = common::clamp(rec.trap, 0.0, 1.0);
+        Lambertian::new((1.0 - t) * self.low + t * self.high)
+    }
+}
+
+impl Material for OrbitTrap {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        self.surface(rec).scatter(r_in, rec, rng)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.surface(rec).eval(r_in, rec, scattered)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.surface(rec).scattering_pdf(r_in, rec, scattered)
+    }
+}
+
+/// Phase function of a medium that scatters light equally in all directions
+pub struct Isotropic {
+    albedo: Color,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        let scattered = Ray::new(rec.p, vec3::random_unit_vector(rng), r_in.time());
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            pdf: self.scattering_pdf(r_in, rec, &scattered),
+            scattered,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.albedo * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * common::PI)
+    }
+}
+
+/// Henyey-Greenstein phase function, for media that scatter light mostly
+/// forwards (`g` towards 1, like haze) or backwards (`g` towards -1)
+pub struct HenyeyGreenstein {
+    albedo: Color,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Color, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein {
+            albedo,
+            g: common::clamp(g, -0.99, 0.99),
+        }
+    }
+
+    // Density of scattering by an angle with the given cosine
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * common::PI * denom * denom.sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        // Invert the distribution of the angle from the ray's own direction
+        let g = self.g;
+        let xi = common::random_double(rng);
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            (1.0 + g * g - s * s) / (2.0 * g)
+        };
+        let sin_theta = f64::sqrt(f64::max(0.0, 1.0 - cos_theta * cos_theta));
+        let phi = 2.0 * common::PI * common::random_double(rng);
+
+        let uvw = Onb::new(r_in.direction());
+        let direction = uvw.local(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            scattered: Ray::new(rec.p, direction, r_in.time()),
+            pdf: self.phase(cos_theta),
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.albedo * self.scattering_pdf(r_in, rec, scattered)
     }
-}
\ No newline at end of file
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = vec3::dot(
+            vec3::unit_vector(r_in.direction()),
+            vec3::unit_vector(scattered.direction()),
+        );
+        self.phase(cos_theta)
+    }
+}
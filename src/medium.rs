@@ -0,0 +1,323 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::common::{self, Rng};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{HenyeyGreenstein, Isotropic, Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use crate::voxel::VoxelGrid;
+
+/// A volume of fog, smoke or similar filling a closed boundary evenly
+///
+/// A ray passing through is scattered after a random distance that grows
+/// shorter the denser the medium, and the phase function (a material such as
+/// `Isotropic` or `HenyeyGreenstein`) picks where it goes next. Rays that
+/// make it through unscattered see whatever lies behind, so thin media look
+/// hazy and dense ones nearly solid. A medium has no surface, so only
+/// `sample_hit` finds it.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    /// Create a medium
+    ///
+    /// # Arguments
+    /// * `boundary` - A closed object enclosing the medium
+    /// * `density` - Chance of scattering per unit distance travelled
+    /// * `phase_function` - How the medium scatters light
+    ///
+    /// # Panics
+    /// If `density` is not positive.
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> ConstantMedium {
+        assert!(density > 0.0, "a medium needs a positive density");
+        ConstantMedium {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+
+    /// Create a medium that scatters equally in all directions
+    pub fn isotropic(boundary: Arc<dyn Hittable>, density: f64, albedo: Color) -> ConstantMedium {
+        ConstantMedium::new(boundary, density, Arc::new(Isotropic::new(albedo)))
+    }
+
+    /// Create a medium that scatters mostly forwards (`g` > 0) or backwards
+    /// (`g` < 0)
+    pub fn henyey_greenstein(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        albedo: Color,
+        g: f64,
+    ) -> ConstantMedium {
+        ConstantMedium::new(
+            boundary,
+            density,
+            Arc::new(HenyeyGreenstein::new(albedo, g)),
+        )
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, _r: &Ray, _t_min: f64, _t_max: f64, _rec: &mut HitRecord) -> bool {
+        false
+    }
+
+    fn sample_hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut Rng,
+    ) -> bool {
+        let len = r.direction().length();
+
+        // Sample the free flight distance afresh in each stretch of the ray
+        // that lies inside the boundary
+        for span in self.boundary.spans(r, t_min, t_max) {
+            let enter = span.enter.t.max(t_min);
+            let exit = span.exit.t.min(t_max);
+            if enter >= exit {
+                continue;
+            }
+
+            let distance = free_flight(rng) / self.density;
+            let t = enter + distance / len;
+            if t < exit {
+                scatter_at(r, t, self.phase_function.clone(), rec);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// A volume of varying density read from a voxel grid, such as a cloud or
+/// the output of a smoke simulation
+///
+/// Rays are scattered by delta tracking: they take random steps sized for
+/// the densest voxel in the grid, and at each step scatter with a chance of
+/// the density there over that maximum, which gives the right distances
+/// without integrating the density along the ray. Grids with an emission or
+/// temperature channel also glow. Like a `ConstantMedium`, a volume is only
+/// found by `sample_hit`.
+pub struct GridVolume {
+    grid: Arc<VoxelGrid>,
+    density_scale: f64,
+    phase_function: Arc<dyn Material>,
+    emission: Color,
+    temperature_scale: f64,
+}
+
+impl GridVolume {
+    /// Create a volume from a grid
+    ///
+    /// # Arguments
+    /// * `grid` - The densities, and any emission and temperature
+    /// * `density_scale` - Factor every density is multiplied by
+    /// * `phase_function` - How the volume scatters light
+    ///
+    /// # Panics
+    /// If `density_scale` is not positive.
+    pub fn new(
+        grid: Arc<VoxelGrid>,
+        density_scale: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> GridVolume {
+        assert!(density_scale > 0.0, "a volume needs a positive density");
+        GridVolume {
+            grid,
+            density_scale,
+            phase_function,
+            emission: Color::new(1.0, 1.0, 1.0),
+            temperature_scale: 1.0,
+        }
+    }
+
+    /// Change the color the emission channel is multiplied by (white by
+    /// default)
+    pub fn with_emission(mut self, emission: Color) -> GridVolume {
+        self.emission = emission;
+        self
+    }
+
+    /// Change how brightly hot regions glow (1 by default)
+    pub fn with_temperature_scale(mut self, temperature_scale: f64) -> GridVolume {
+        self.temperature_scale = temperature_scale;
+        self
+    }
+
+    // Light given off where a ray collides with the volume at `p`
+    fn glow(&self, p: Point3) -> Color {
+        let mut glow = Color::new(0.0, 0.0, 0.0);
+        if self.grid.has_emission() {
+            glow += self.grid.emission(p) * self.emission;
+        }
+        if self.grid.has_temperature() {
+            glow += self.temperature_scale * blackbody(self.grid.temperature(p));
+        }
+        glow
+    }
+}
+
+impl Hittable for GridVolume {
+    fn hit(&self, _r: &Ray, _t_min: f64, _t_max: f64, _rec: &mut HitRecord) -> bool {
+        false
+    }
+
+    fn sample_hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut Rng,
+    ) -> bool {
+        let majorant = self.density_scale * self.grid.max_density();
+        if majorant <= 0.0 {
+            return false;
+        }
+        let Some((enter, exit)) = self.grid.bounds().clip(r, t_min, t_max) else {
+            return false;
+        };
+        let len = r.direction().length();
+
+        let mut t = enter;
+        loop {
+            t += free_flight(rng) / (majorant * len);
+            if t >= exit {
+                return false;
+            }
+            // A real collision, rather than a null one that carries on
+            // unchanged
+            let p = r.at(t);
+            if common::random_double(rng) * majorant < self.density_scale * self.grid.density(p) {
+                let glow = self.glow(p);
+                let mat: Arc<dyn Material> = if glow.length_squared() > 0.0 {
+                    Arc::new(Glowing {
+                        phase_function: self.phase_function.clone(),
+                        emit: glow,
+                    })
+                } else {
+                    self.phase_function.clone()
+                };
+                scatter_at(r, t, mat, rec);
+                return true;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.grid.bounds())
+    }
+}
+
+// Phase function of a point in a volume that also gives off light
+struct Glowing {
+    phase_function: Arc<dyn Material>,
+    emit: Color,
+}
+
+impl Material for Glowing {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<ScatterRecord> {
+        self.phase_function.scatter(r_in, rec, rng)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.phase_function.eval(r_in, rec, scattered)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.phase_function.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        self.emit
+    }
+}
+
+// Fill in a hit record for a ray scattering inside a volume at `t`
+fn scatter_at(r: &Ray, t: f64, mat: Arc<dyn Material>, rec: &mut HitRecord) {
+    rec.t = t;
+    rec.p = r.at(t);
+    // A point in a volume has no surface, so any normal will do
+    rec.normal = Vec3::new(1.0, 0.0, 0.0);
+    rec.front_face = true;
+    rec.mat = Some(mat);
+}
+
+// Glow of matter at the given temperature in kelvin: the color of a black
+// body, with brightness rising as the fourth power of the temperature. Below
+// the Draper point, where hot objects start to glow visibly, there is none
+fn blackbody(kelvin: f64) -> Color {
+    const DRAPER_POINT: f64 = 798.0;
+    if kelvin < DRAPER_POINT {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    // Planck's law at wavelengths (in meters) standing in for red, green
+    // and blue, normalised so the brightest is 1
+    let planck = |wavelength: f64| {
+        1.0 / (wavelength.powi(5) * f64::exp_m1(1.4388e-2 / (wavelength * kelvin)))
+    };
+    let color = Color::new(planck(610e-9), planck(550e-9), planck(465e-9));
+    let brightest = color.x().max(color.y()).max(color.z());
+    (kelvin / 1000.0).powi(4) / brightest * color
+}
+
+// Distance to the next collision in a medium of unit density
+fn free_flight(rng: &mut Rng) -> f64 {
+    -f64::ln(1.0 - common::random_double(rng))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    // Fraction of `n` rays along the Z axis that pass through `volume`
+    fn transmittance(volume: &dyn Hittable, n: usize, seed: u64) -> f64 {
+        let mut rng = common::pixel_rng(seed, 0);
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -2.0), 0.0);
+        let passed = (0..n)
+            .filter(|_| {
+                let mut rec = HitRecord::new();
+                !volume.sample_hit(&r, 0.001, common::INFINITY, &mut rec, &mut rng)
+            })
+            .count();
+        passed as f64 / n as f64
+    }
+
+    #[test]
+    fn constant_medium_transmittance() {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let boundary = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, mat));
+        let medium = ConstantMedium::isotropic(boundary, 0.7, Color::new(1.0, 1.0, 1.0));
+
+        // Through the center the ray crosses 2 units of the medium
+        let t = transmittance(&medium, 100_000, 1);
+        assert!((t - f64::exp(-1.4)).abs() < 0.01, "{}", t);
+        assert_eq!(t, transmittance(&medium, 100_000, 1));
+        assert!(!medium.hit(
+            &Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0),
+            0.001,
+            common::INFINITY,
+            &mut HitRecord::new()
+        ));
+    }
+}
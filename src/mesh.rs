@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::triangle;
+use crate::vec3::{self, Point3, Vec3};
+
+/// One triangle of a mesh, as indices into the mesh's buffers
+#[derive(Clone, Copy)]
+pub struct Face {
+    pub positions: [usize; 3],
+    // Vertex normals for smooth shading; flat shading without them
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+/// Triangle mesh whose faces share vertex, normal and texture coordinate buffers
+pub struct Mesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<Face>,
+    mat: Arc<dyn Material>,
+}
+
+impl Mesh {
+    /// Create a mesh from its buffers
+    ///
+    /// # Arguments
+    /// * `positions` - Vertex positions
+    /// * `normals` - Vertex normals, need not be unit length
+    /// * `uvs` - Texture coordinates
+    /// * `faces` - Triangles indexing into the three buffers
+    /// * `mat` - The material of the whole mesh
+    ///
+    /// # Panics
+    /// If a face refers past the end of one of the buffers.
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<Face>,
+        mat: Arc<dyn Material>,
+    ) -> Mesh {
+        for face in &faces {
+            assert!(face.positions.iter().all(|&i| i < positions.len()));
+            assert!(face
+                .normals
+                .is_none_or(|n| n.iter().all(|&i| i < normals.len())));
+            assert!(face.uvs.is_none_or(|uv| uv.iter().all(|&i| i < uvs.len())));
+        }
+        Mesh {
+            positions,
+            normals,
+            uvs,
+            faces,
+            mat,
+        }
+    }
+
+    pub fn faces(&self) -> &[Face] {
+        &self.faces
+    }
+
+    /// One hittable per face, all sharing this mesh's buffers
+    pub fn triangles(self: &Arc<Self>) -> HittableList {
+        let mut list = HittableList::new();
+        for face in 0..self.faces.len() {
+            list.add(Arc::new(MeshTriangle {
+                mesh: self.clone(),
+                face,
+            }));
+        }
+        list
+    }
+}
+
+/// A single face of a `Mesh`
+pub struct MeshTriangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let mesh = &self.mesh;
+        let face = &mesh.faces[self.face];
+        let [a, b, c] = face.positions.map(|i| mesh.positions[i]);
+        let Some((t, w)) = triangle::intersect(r, a, b, c, t_min, t_max) else {
+            return false;
+        };
+
+        rec.t = t;
+        rec.p = r.at(t);
+        (rec.u, rec.v) = match face.uvs {
+            Some(uvs) => {
+                let [uv0, uv1, uv2] = uvs.map(|i| mesh.uvs[i]);
+                (
+                    w[0] * uv0.0 + w[1] * uv1.0 + w[2] * uv2.0,
+                    w[0] * uv0.1 + w[1] * uv1.1 + w[2] * uv2.1,
+                )
+            }
+            None => (w[1], w[2]),
+        };
+
+        let mut geometric = vec3::unit_vector(vec3::cross(b - a, c - a));
+        match face.normals {
+            Some(normals) => {
+                let [n0, n1, n2] = normals.map(|i| mesh.normals[i]);
+                let shading = vec3::unit_vector(w[0] * n0 + w[1] * n1 + w[2] * n2);
+                // Decide the side from the true surface, so that interpolated
+                // normals never let a ray hit the back of a face from the front
+                if vec3::dot(geometric, shading) < 0.0 {
+                    geometric = -geometric;
+                }
+                rec.set_face_normal(r, geometric);
+                rec.normal = if rec.front_face { shading } else { -shading };
+            }
+            None => rec.set_face_normal(r, geometric),
+        }
+        rec.mat = Some(mesh.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mesh = &self.mesh;
+        let [a, b, c] = mesh.faces[self.face].positions.map(|i| mesh.positions[i]);
+        Some(aabb::surrounding_box(Aabb::new(a, b), Aabb::new(c, c)))
+    }
+}
@@ -0,0 +1,265 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::material::Material;
+use crate::mesh::{Face, Mesh};
+use crate::vec3::{Point3, Vec3};
+
+// Wavefront OBJ support covers the geometry statements:
+//
+//   v x y z        vertex position (further values are ignored)
+//   vt u [v]       texture coordinate
+//   vn x y z       vertex normal
+//   f v/vt/vn ...  polygon, each vertex as `v`, `v/vt`, `v//vn` or `v/vt/vn`
+//
+// Indices start at 1, and negative indices count back from the most recent
+// element. Polygons are split into fans of triangles. Everything else
+// (groups, smoothing, materials) is ignored.
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(err) => write!(f, "cannot read OBJ file: {}", err),
+            ObjError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<io::Error> for ObjError {
+    fn from(err: io::Error) -> ObjError {
+        ObjError::Io(err)
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> ObjError {
+    ObjError::Parse {
+        line,
+        message: message.into(),
+    }
+}
+
+/// Load a mesh from an OBJ file on disk
+pub fn load(path: impl AsRef<Path>, mat: Arc<dyn Material>) -> Result<Mesh, ObjError> {
+    let text = fs::read_to_string(path)?;
+    parse(&text, mat)
+}
+
+/// Build a mesh from the text of an OBJ file
+pub fn parse(text: &str, mat: Arc<dyn Material>) -> Result<Mesh, ObjError> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut faces = Vec::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = raw.split('#').next().unwrap_or("");
+        let mut tokens = content.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let n = numbers(line, keyword, &args, 3, 3)?;
+                positions.push(Point3::new(n[0], n[1], n[2]));
+            }
+            "vn" => {
+                let n = numbers(line, keyword, &args, 3, 3)?;
+                normals.push(Vec3::new(n[0], n[1], n[2]));
+            }
+            "vt" => {
+                let uv = numbers(line, keyword, &args, 1, 2)?;
+                uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error(line, "a face needs at least three vertices"));
+                }
+                let mut polygon = Vec::with_capacity(args.len());
+                for arg in &args {
+                    polygon.push(face_vertex(
+                        line,
+                        arg,
+                        positions.len(),
+                        uvs.len(),
+                        normals.len(),
+                    )?);
+                }
+
+                // Normals and texture coordinates are used only if every vertex has them
+                let all_uvs = polygon.iter().all(|v| v.1.is_some());
+                let all_normals = polygon.iter().all(|v| v.2.is_some());
+                for k in 1..polygon.len() - 1 {
+                    let corners = [polygon[0], polygon[k], polygon[k + 1]];
+                    faces.push(Face {
+                        positions: corners.map(|v| v.0),
+                        uvs: all_uvs.then(|| corners.map(|v| v.1.unwrap())),
+                        normals: all_normals.then(|| corners.map(|v| v.2.unwrap())),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Mesh::new(positions, normals, uvs, faces, mat))
+}
+
+// Parse at least `min` numbers, keeping at most `max` of them
+fn numbers(
+    line: usize,
+    keyword: &str,
+    args: &[&str],
+    min: usize,
+    max: usize,
+) -> Result<Vec<f64>, ObjError> {
+    if args.len() < min {
+        return Err(error(
+            line,
+            format!("`{}` needs at least {} numbers", keyword, min),
+        ));
+    }
+    args.iter()
+        .take(max)
+        .map(|arg| {
+            arg.parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| error(line, format!("invalid number `{}`", arg)))
+        })
+        .collect()
+}
+
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+// Parse `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero-based indices
+fn face_vertex(
+    line: usize,
+    arg: &str,
+    positions: usize,
+    uvs: usize,
+    normals: usize,
+) -> Result<FaceVertex, ObjError> {
+    let mut parts = arg.split('/');
+    let position = parts.next().unwrap_or("");
+    let uv = parts.next().filter(|s| !s.is_empty());
+    let normal = parts.next().filter(|s| !s.is_empty());
+    if parts.next().is_some() {
+        return Err(error(line, format!("malformed face vertex `{}`", arg)));
+    }
+
+    Ok((
+        resolve_index(line, position, positions, "vertex")?,
+        uv.map(|s| resolve_index(line, s, uvs, "texture coordinate"))
+            .transpose()?,
+        normal
+            .map(|s| resolve_index(line, s, normals, "normal"))
+            .transpose()?,
+    ))
+}
+
+fn resolve_index(line: usize, s: &str, count: usize, what: &str) -> Result<usize, ObjError> {
+    let index: i64 = s
+        .parse()
+        .map_err(|_| error(line, format!("invalid {} index `{}`", what, s)))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(error(
+            line,
+            format!("{} index {} is out of range", what, index),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn parse_text(text: &str) -> Result<Mesh, ObjError> {
+        parse(text, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))))
+    }
+
+    fn error_line(text: &str) -> usize {
+        match parse_text(text) {
+            Err(ObjError::Parse { line, .. }) => line,
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("mesh parsed"),
+        }
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_latest() {
+        let text = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 1
+f -3/-3/-1 -2/-2/-1 -1/-1/-1
+v 0 0 1
+v 1 0 1
+v 0 1 1
+vn 0 1 0
+f -3//-1 -2//-1 -1//-1
+f 1/1/1 -2//2 6
+";
+        let mesh = parse_text(text).unwrap();
+        let faces = mesh.faces();
+        assert_eq!(faces.len(), 3);
+
+        assert_eq!(faces[0].positions, [0, 1, 2]);
+        assert_eq!(faces[0].uvs, Some([0, 1, 2]));
+        assert_eq!(faces[0].normals, Some([0, 0, 0]));
+
+        // Relative to the elements declared so far, not the whole file
+        assert_eq!(faces[1].positions, [3, 4, 5]);
+        assert_eq!(faces[1].uvs, None);
+        assert_eq!(faces[1].normals, Some([1, 1, 1]));
+
+        // Absolute and relative indices mixed in one face; without a normal
+        // and uv on every vertex, the face has neither
+        assert_eq!(faces[2].positions, [0, 4, 5]);
+        assert_eq!(faces[2].uvs, None);
+        assert_eq!(faces[2].normals, None);
+    }
+
+    #[test]
+    fn polygons_split_into_fans() {
+        let mesh = parse_text("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf -4 -3 -2 -1\n").unwrap();
+        let positions: Vec<_> = mesh.faces().iter().map(|f| f.positions).collect();
+        assert_eq!(positions, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_errors() {
+        let vertices = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+        assert_eq!(error_line(&format!("{}f 0 1 2\n", vertices)), 4);
+        assert_eq!(error_line(&format!("{}f 1 2 4\n", vertices)), 4);
+        assert_eq!(error_line(&format!("{}f -4 -2 -1\n", vertices)), 4);
+        assert_eq!(error_line(&format!("{}\nf 1/-1 2/1 3/1\n", vertices)), 5);
+        // A vertex declared after the face does not count
+        assert_eq!(error_line("v 0 0 0\nv 1 0 0\nf 1 2 3\nv 0 1 0\n"), 3);
+    }
+}
@@ -0,0 +1,39 @@
+use crate::vec3::{self, Vec3};
+
+/// Orthonormal basis, used to turn directions sampled around the Z axis into
+/// directions around an arbitrary axis
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    /// Build a basis whose `w` axis points along `n`
+    pub fn new(n: Vec3) -> Onb {
+        let w = vec3::unit_vector(n);
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = vec3::unit_vector(vec3::cross(w, a));
+        let u = vec3::cross(w, v);
+        Onb { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+
+    /// Convert coordinates in this basis to world space
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u() + a.y() * self.v() + a.z() * self.w()
+    }
+}
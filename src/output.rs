@@ -0,0 +1,213 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::color;
+use crate::film::Film;
+use crate::png::{BitDepth, PngWriter};
+
+/// Encodes a film into an image file format
+pub trait ImageWriter {
+    fn write(&self, out: &mut dyn Write, film: &Film) -> io::Result<()>;
+}
+
+/// ASCII PPM, gamma-corrected 8-bit color
+pub struct P3Writer;
+
+/// Binary PPM, gamma-corrected 8-bit color
+pub struct P6Writer;
+
+/// Portable float map: linear, unclamped 32-bit float color
+pub struct PfmWriter;
+
+impl ImageWriter for P3Writer {
+    fn write(&self, out: &mut dyn Write, film: &Film) -> io::Result<()> {
+        write!(out, "P3\n{} {}\n255\n", film.width(), film.height())?;
+        for y in 0..film.height() {
+            for x in 0..film.width() {
+                let c = color::gamma_correct(film.pixel(x, y));
+                // Write the translated [0, 255] value of each color component
+                writeln!(
+                    out,
+                    "{} {} {}",
+                    color::to_u8(c.x()),
+                    color::to_u8(c.y()),
+                    color::to_u8(c.z())
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ImageWriter for P6Writer {
+    fn write(&self, out: &mut dyn Write, film: &Film) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", film.width(), film.height())?;
+        let mut row = Vec::with_capacity(3 * film.width());
+        for y in 0..film.height() {
+            row.clear();
+            for x in 0..film.width() {
+                let c = color::gamma_correct(film.pixel(x, y));
+                row.extend_from_slice(&[
+                    color::to_u8(c.x()),
+                    color::to_u8(c.y()),
+                    color::to_u8(c.z()),
+                ]);
+            }
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+}
+
+impl ImageWriter for PfmWriter {
+    fn write(&self, out: &mut dyn Write, film: &Film) -> io::Result<()> {
+        // A negative scale marks little-endian data
+        write!(out, "PF\n{} {}\n-1.0\n", film.width(), film.height())?;
+        let mut row = Vec::with_capacity(12 * film.width());
+        // PFM stores its rows from the bottom of the image up
+        for y in (0..film.height()).rev() {
+            row.clear();
+            for x in 0..film.width() {
+                let c = film.pixel(x, y);
+                for v in [c.x(), c.y(), c.z()] {
+                    row.extend_from_slice(&(v as f32).to_le_bytes());
+                }
+            }
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    P3,
+    P6,
+    Pfm,
+    Png,
+}
+
+impl OutputFormat {
+    /// Guess the format from a file name's extension
+    pub fn from_path(path: &str) -> Option<OutputFormat> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(OutputFormat::P3),
+            "pfm" => Some(OutputFormat::Pfm),
+            "png" => Some(OutputFormat::Png),
+            _ => None,
+        }
+    }
+
+    /// Look up a format by name: `p3`, `p6`, `pfm` or `png`
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "p3" => Some(OutputFormat::P3),
+            "p6" => Some(OutputFormat::P6),
+            "pfm" => Some(OutputFormat::Pfm),
+            "png" => Some(OutputFormat::Png),
+            _ => None,
+        }
+    }
+
+    /// File name extension for images in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::P3 | OutputFormat::P6 => "ppm",
+            OutputFormat::Pfm => "pfm",
+            OutputFormat::Png => "png",
+        }
+    }
+
+    pub fn writer(&self, bit_depth: BitDepth, alpha: bool) -> Box<dyn ImageWriter> {
+        match self {
+            OutputFormat::P3 => Box::new(P3Writer),
+            OutputFormat::P6 => Box::new(P6Writer),
+            OutputFormat::Pfm => Box::new(PfmWriter),
+            OutputFormat::Png => Box::new(PngWriter { bit_depth, alpha }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    // 3 x 2 film, with colors (x / 4, 0, 1) in the top row and out of the
+    // displayable range in the bottom row
+    fn film() -> Film {
+        let mut film = Film::new(3, 2);
+        for x in 0..3 {
+            let c = Color::new(x as f64 / 4.0, 0.0, 1.0);
+            film.add_samples(x, 0, 2.0 * c, 2, 2);
+            film.add_samples(x, 1, Color::new(4.0, -0.5, x as f64), 1, 1);
+        }
+        film
+    }
+
+    fn encode(writer: &dyn ImageWriter) -> Vec<u8> {
+        let mut out = Vec::new();
+        writer.write(&mut out, &film()).unwrap();
+        out
+    }
+
+    #[test]
+    fn p6_header_and_bytes() {
+        let out = encode(&P6Writer);
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&out[..header.len()], header);
+        assert_eq!(out.len(), header.len() + 3 * 3 * 2);
+        // Gamma-corrected and clamped, top row first
+        assert_eq!(
+            &out[header.len()..],
+            &[
+                0, 0, 255, 128, 0, 255, 181, 0, 255, //
+                255, 0, 0, 255, 0, 255, 255, 0, 255,
+            ]
+        );
+    }
+
+    #[test]
+    fn p3_matches_p6() {
+        let text = String::from_utf8(encode(&P3Writer)).unwrap();
+        let mut words = text.split_whitespace();
+        assert_eq!(words.next(), Some("P3"));
+        let numbers: Vec<u8> = words.map(|w| w.parse().unwrap()).collect();
+        let binary = encode(&P6Writer);
+        // Width, height and maximum value, then the same bytes
+        assert_eq!(numbers[..3], [3, 2, 255]);
+        assert_eq!(numbers[3..], binary[b"P6\n3 2\n255\n".len()..]);
+    }
+
+    #[test]
+    fn pfm_is_little_endian_from_the_bottom_row() {
+        let out = encode(&PfmWriter);
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&out[..header.len()], header);
+        assert_eq!(out.len(), header.len() + 4 * 3 * 3 * 2);
+
+        let values: Vec<f32> = out[header.len()..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        // Linear and unclamped, bottom row first
+        assert_eq!(
+            values,
+            [
+                4.0, -0.5, 0.0, 4.0, -0.5, 1.0, 4.0, -0.5, 2.0, //
+                0.0, 0.0, 1.0, 0.25, 0.0, 1.0, 0.5, 0.0, 1.0,
+            ]
+        );
+    }
+
+    #[test]
+    fn formats_from_names_and_paths() {
+        assert!(OutputFormat::from_path("a/b.PFM") == Some(OutputFormat::Pfm));
+        assert!(OutputFormat::from_path("image.ppm") == Some(OutputFormat::P3));
+        assert!(OutputFormat::from_path("image.jpg").is_none());
+        assert!(OutputFormat::from_path("image").is_none());
+        assert!(OutputFormat::from_name("P6") == Some(OutputFormat::P6));
+        assert_eq!(OutputFormat::P6.extension(), "ppm");
+    }
+}
@@ -1,24 +1,26 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::hittable::{HitRecord, Hittable};
+use crate::aabb::Aabb;
+use crate::common;
+use crate::hittable::{HitRecord, Hittable, Span};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::{self, Point3, Vec3};
 
 pub struct Plane {
-    point: Point3,      // A point on the plane
-    normal: Vec3,       // Normal vector of the plane
-    mat: Rc<dyn Material>,
+    point: Point3, // A point on the plane
+    normal: Vec3,  // Normal vector of the plane
+    mat: Arc<dyn Material>,
 }
 
 impl Plane {
     /// Create a plane defined by a point and a normal vector
-    /// 
+    ///
     /// # Arguments
     /// * `point` - A point that lies on the plane
     /// * `normal` - The normal vector (perpendicular to the plane)
     /// * `mat` - The material of the plane
-    pub fn new(point: Point3, normal: Vec3, mat: Rc<dyn Material>) -> Plane {
+    pub fn new(point: Point3, normal: Vec3, mat: Arc<dyn Material>) -> Plane {
         Plane {
             point,
             normal: vec3::unit_vector(normal),
@@ -27,11 +29,11 @@ impl Plane {
     }
 
     /// Create a horizontal plane at a given height (Y-axis)
-    /// 
+    ///
     /// # Arguments
     /// * `height` - The Y coordinate of the plane
     /// * `mat` - The material of the plane
-    pub fn horizontal(height: f64, mat: Rc<dyn Material>) -> Plane {
+    pub fn horizontal(height: f64, mat: Arc<dyn Material>) -> Plane {
         Plane {
             point: Point3::new(0.0, height, 0.0),
             normal: Vec3::new(0.0, 1.0, 0.0),
@@ -40,11 +42,11 @@ impl Plane {
     }
 
     /// Create a vertical plane perpendicular to the Z-axis
-    /// 
+    ///
     /// # Arguments
     /// * `z_position` - The Z coordinate of the plane
     /// * `mat` - The material of the plane
-    pub fn vertical_z(z_position: f64, mat: Rc<dyn Material>) -> Plane {
+    pub fn vertical_z(z_position: f64, mat: Arc<dyn Material>) -> Plane {
         Plane {
             point: Point3::new(0.0, 0.0, z_position),
             normal: Vec3::new(0.0, 0.0, 1.0),
@@ -53,11 +55,11 @@ impl Plane {
     }
 
     /// Create a vertical plane perpendicular to the X-axis
-    /// 
+    ///
     /// # Arguments
     /// * `x_position` - The X coordinate of the plane
     /// * `mat` - The material of the plane
-    pub fn vertical_x(x_position: f64, mat: Rc<dyn Material>) -> Plane {
+    pub fn vertical_x(x_position: f64, mat: Arc<dyn Material>) -> Plane {
         Plane {
             point: Point3::new(x_position, 0.0, 0.0),
             normal: Vec3::new(1.0, 0.0, 0.0),
@@ -68,29 +70,78 @@ impl Plane {
 
 impl Hittable for Plane {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let ray_origin = r.origin();
-        let ray_dir = r.direction();
-        
-        // Denominator: dot product of ray direction and plane normal
-        let denom = vec3::dot(ray_dir, self.normal);
-        
-        // If denominator is close to 0, ray is parallel to plane
-        if denom.abs() < 1e-8 {
+        let Some(t) = intersect(r, self.point, self.normal, t_min, t_max) else {
             return false;
-        }
-        
-        // Calculate t: (point - origin) · normal / (direction · normal)
-        let t = vec3::dot(self.point - ray_origin, self.normal) / denom;
-        
-        // Check if t is within the acceptable range
-        if t < t_min || t > t_max {
-            return false;
-        }
-        
+        };
+
         rec.t = t;
         rec.p = r.at(rec.t);
         rec.set_face_normal(r, self.normal);
         rec.mat = Some(self.mat.clone());
         true
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // An infinite plane has no finite bounds
+        None
+    }
+
+    // As a solid, a plane is the half-space behind its normal
+    fn spans(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<Span> {
+        let mut rec = HitRecord::new();
+        if self.hit(r, t_min, t_max, &mut rec) {
+            let mut span = Span::everywhere();
+            if rec.front_face {
+                span.enter = rec;
+            } else {
+                span.exit = rec;
+            }
+            vec![span]
+        } else if vec3::dot(r.at(t_min) - self.point, self.normal) < 0.0 {
+            vec![Span::everywhere()]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Ray parameter where the ray crosses the plane through `point` with the
+/// given normal, if that lies within the acceptable range
+///
+/// The finite planar shapes build on this, adding their own outline test.
+pub fn intersect(r: &Ray, point: Point3, normal: Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+    // Denominator: dot product of ray direction and plane normal
+    let denom = vec3::dot(r.direction(), normal);
+
+    // If denominator is close to 0, ray is parallel to plane
+    if denom.abs() < 1e-8 {
+        return None;
+    }
+
+    // Calculate t: (point - origin) · normal / (direction · normal)
+    let t = vec3::dot(point - r.origin(), normal) / denom;
+
+    // Check if t is within the acceptable range
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some(t)
+}
+
+/// Density per unit solid angle of picking `direction` from `origin` when
+/// points are chosen uniformly over the area of a flat `shape`
+pub fn solid_angle_pdf(shape: &dyn Hittable, area: f64, origin: Point3, direction: Vec3) -> f64 {
+    let mut rec = HitRecord::new();
+    if !shape.hit(
+        &Ray::new(origin, direction, 0.0),
+        0.001,
+        common::INFINITY,
+        &mut rec,
+    ) {
+        return 0.0;
+    }
+
+    let distance_squared = rec.t * rec.t * direction.length_squared();
+    let cosine = (vec3::dot(direction, rec.normal) / direction.length()).abs();
+    distance_squared / (cosine * area)
 }
@@ -0,0 +1,430 @@
+use std::io::{self, Write};
+
+use crate::color;
+use crate::film::Film;
+use crate::output::ImageWriter;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+/// PNG, gamma-corrected 8- or 16-bit color
+pub struct PngWriter {
+    pub bit_depth: BitDepth,
+    // Write an alpha channel taken from the film's coverage
+    pub alpha: bool,
+}
+
+impl ImageWriter for PngWriter {
+    fn write(&self, out: &mut dyn Write, film: &Film) -> io::Result<()> {
+        let channels = if self.alpha { 4 } else { 3 };
+        let bytes_per_sample = match self.bit_depth {
+            BitDepth::Eight => 1,
+            BitDepth::Sixteen => 2,
+        };
+        let bytes_per_pixel = channels * bytes_per_sample;
+
+        // Raw scanlines, each prefixed by its filter type
+        let row_len = film.width() * bytes_per_pixel;
+        let mut raw = Vec::with_capacity((row_len + 1) * film.height());
+        let mut previous = vec![0u8; row_len];
+        let mut row = Vec::with_capacity(row_len);
+        for y in 0..film.height() {
+            row.clear();
+            for x in 0..film.width() {
+                let c = color::gamma_correct(film.pixel(x, y));
+                // Alpha is coverage, which is not gamma-corrected
+                let samples = [c.x(), c.y(), c.z(), film.alpha(x, y)];
+                for &s in &samples[..channels] {
+                    match self.bit_depth {
+                        BitDepth::Eight => row.push(color::to_u8(s)),
+                        BitDepth::Sixteen => row.extend_from_slice(&color::to_u16(s).to_be_bytes()),
+                    }
+                }
+            }
+            filter_row(&row, &previous, bytes_per_pixel, &mut raw);
+            std::mem::swap(&mut previous, &mut row);
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(film.width() as u32).to_be_bytes());
+        header.extend_from_slice(&(film.height() as u32).to_be_bytes());
+        header.push(8 * bytes_per_sample as u8);
+        header.push(if self.alpha { 6 } else { 2 }); // Truecolor, with or without alpha
+        header.extend_from_slice(&[0, 0, 0]); // Deflate, adaptive filtering, no interlace
+
+        out.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])?;
+        write_chunk(out, b"IHDR", &header)?;
+        write_chunk(out, b"IDAT", &zlib_compress(&raw))?;
+        write_chunk(out, b"IEND", &[])
+    }
+}
+
+fn write_chunk(out: &mut dyn Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32_update(crc32_update(0xffff_ffff, kind), data) ^ 0xffff_ffff;
+    out.write_all(&crc.to_be_bytes())
+}
+
+// Filtering
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// Append `row` to `out` using whichever of the five PNG filters gives the
+// smallest sum of absolute differences, the usual heuristic for good compression
+fn filter_row(row: &[u8], previous: &[u8], bpp: usize, out: &mut Vec<u8>) {
+    let mut best: Option<(u64, u8, Vec<u8>)> = None;
+    for filter in 0..5u8 {
+        let filtered: Vec<u8> = (0..row.len())
+            .map(|i| {
+                let a = if i >= bpp { row[i - bpp] } else { 0 };
+                let b = previous[i];
+                let c = if i >= bpp { previous[i - bpp] } else { 0 };
+                let predictor = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                row[i].wrapping_sub(predictor)
+            })
+            .collect();
+        let cost = filtered
+            .iter()
+            .map(|&v| (v as i8).unsigned_abs() as u64)
+            .sum();
+        if best
+            .as_ref()
+            .is_none_or(|(best_cost, _, _)| cost < *best_cost)
+        {
+            best = Some((cost, filter, filtered));
+        }
+    }
+
+    let (_, filter, filtered) = best.unwrap();
+    out.push(filter);
+    out.extend_from_slice(&filtered);
+}
+
+// Checksums
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc = CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest block for which the sums cannot overflow
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+// Deflate: LZ77 matching followed by the fixed Huffman code of RFC 1951
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    // Append the low `n` bits of `value`, least significant bit first
+    fn write_bits(&mut self, value: u32, n: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit
+    fn write_code(&mut self, code: u32, len: u32) {
+        let reversed = code.reverse_bits() >> (32 - len);
+        self.write_bits(reversed, len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn write_literal(bits: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => bits.write_code(0x30 + symbol, 8),
+        144..=255 => bits.write_code(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(bits: &mut BitWriter, length: usize, distance: usize) {
+    let li = LENGTH_BASE
+        .iter()
+        .rposition(|&b| b as usize <= length)
+        .unwrap();
+    write_literal(bits, 257 + li as u32);
+    bits.write_bits(
+        (length - LENGTH_BASE[li] as usize) as u32,
+        LENGTH_EXTRA[li] as u32,
+    );
+
+    let di = DIST_BASE
+        .iter()
+        .rposition(|&b| b as usize <= distance)
+        .unwrap();
+    bits.write_code(di as u32, 5);
+    bits.write_bits(
+        (distance - DIST_BASE[di] as usize) as u32,
+        DIST_EXTRA[di] as u32,
+    );
+}
+
+fn hash3(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter {
+        bytes: Vec::with_capacity(data.len() / 2),
+        buffer: 0,
+        count: 0,
+    };
+    // A single final block using the fixed Huffman code
+    bits.write_bits(1, 1);
+    bits.write_bits(1, 2);
+
+    // Most recent position of each hash, and the previous position with the same hash
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+    let insert = |head: &mut Vec<usize>, prev: &mut Vec<usize>, i: usize| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash3(data, i);
+            prev[i % WINDOW_SIZE] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if i + MIN_MATCH <= data.len() {
+            let max_len = usize::min(MAX_MATCH, data.len() - i);
+            let mut candidate = head[hash3(data, i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[i..i + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW_SIZE];
+                // Stop once the chain leaves the window or wraps onto newer entries
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            write_match(&mut bits, best_len, best_dist);
+            for j in i..i + best_len {
+                insert(&mut head, &mut prev, j);
+            }
+            i += best_len;
+        } else {
+            write_literal(&mut bits, data[i] as u32);
+            insert(&mut head, &mut prev, i);
+            i += 1;
+        }
+    }
+
+    write_literal(&mut bits, 256); // End of block
+    bits.finish()
+}
+
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // 32K window, deflate, no preset dictionary; 0x785e is a multiple of 31
+    let mut out = vec![0x78, 0x5e];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Decoder for the single fixed Huffman block that `deflate` writes
+    fn inflate_fixed(data: &[u8]) -> Vec<u8> {
+        let mut pos = 0;
+        let mut read_bits = |n: u32| -> u32 {
+            let mut value = 0;
+            for k in 0..n {
+                let bit = (data[pos / 8] >> (pos % 8)) & 1;
+                value |= (bit as u32) << k;
+                pos += 1;
+            }
+            value
+        };
+        assert_eq!(read_bits(3), 0b011, "expected a final fixed Huffman block");
+
+        let mut out = Vec::new();
+        loop {
+            // Codes are read most significant bit first, 7 to 9 bits long
+            let mut code = 0;
+            let mut len = 0;
+            let symbol = loop {
+                code = code << 1 | read_bits(1);
+                len += 1;
+                match (len, code) {
+                    (7, 0..=0x17) => break code + 256,
+                    (8, 0x30..=0xbf) => break code - 0x30,
+                    (8, 0xc0..=0xc7) => break code - 0xc0 + 280,
+                    (9, 0x190..=0x1ff) => break code - 0x190 + 144,
+                    _ => assert!(len < 9, "invalid code"),
+                }
+            };
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => return out,
+                _ => {
+                    let li = (symbol - 257) as usize;
+                    let length =
+                        LENGTH_BASE[li] as usize + read_bits(LENGTH_EXTRA[li] as u32) as usize;
+                    let di = (read_bits(5).reverse_bits() >> 27) as usize;
+                    let distance =
+                        DIST_BASE[di] as usize + read_bits(DIST_EXTRA[di] as u32) as usize;
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        let crc32 = |data: &[u8]| crc32_update(0xffff_ffff, data) ^ 0xffff_ffff;
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        // Long enough to need the modulus
+        let zeros_and_ones: Vec<u8> = (0..100_000).map(|i| (i % 2 * 255) as u8).collect();
+        let (mut a, mut b) = (1u64, 0u64);
+        for &byte in &zeros_and_ones {
+            a = (a + byte as u64) % 65521;
+            b = (b + a) % 65521;
+        }
+        assert_eq!(adler32(&zeros_and_ones), (b << 16 | a) as u32);
+    }
+
+    #[test]
+    fn deflate_matches_known_output() {
+        // As written by zlib with its fixed Huffman strategy
+        assert_eq!(deflate(b"a"), [0x4b, 0x04, 0x00]);
+        assert_eq!(deflate(b"abc"), [0x4b, 0x4c, 0x4a, 0x06, 0x00]);
+    }
+
+    #[test]
+    fn deflate_round_trips() {
+        let text = b"a rose is a rose is a rose is a rose".repeat(50);
+        let noise: Vec<u8> = (0..70_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        let runs: Vec<u8> = (0..40_000).map(|i| (i / 1000) as u8).collect();
+        for data in [&b""[..], &text, &noise, &runs] {
+            let compressed = deflate(data);
+            assert_eq!(inflate_fixed(&compressed), data);
+        }
+        assert!(deflate(&text).len() < text.len() / 10);
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::common::{self, Rng};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::plane;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+/// A parallelogram, most often used as a rectangular area light
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    mat: Arc<dyn Material>,
+    normal: Vec3,
+    // Scaled normal used to find the planar coordinates of a hit point
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    /// Create a parallelogram from one corner and its two edges
+    ///
+    /// # Arguments
+    /// * `q` - One corner of the parallelogram
+    /// * `u` - The edge from `q` to the second corner
+    /// * `v` - The edge from `q` to the fourth corner
+    /// * `mat` - The material of the parallelogram
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Quad {
+        let n = vec3::cross(u, v);
+        let normal = vec3::unit_vector(n);
+        Quad {
+            q,
+            u,
+            v,
+            mat,
+            normal,
+            w: n / vec3::dot(n, n),
+            area: n.length(),
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let Some(t) = plane::intersect(r, self.q, self.normal, t_min, t_max) else {
+            return false;
+        };
+
+        // Planar coordinates of the hit point, both in [0, 1] inside the quad
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = vec3::dot(self.w, vec3::cross(planar, self.v));
+        let beta = vec3::dot(self.w, vec3::cross(self.u, planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.set_face_normal(r, self.normal);
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let diagonal1 = Aabb::new(self.q, self.q + self.u + self.v);
+        let diagonal2 = Aabb::new(self.q + self.u, self.q + self.v);
+        Some(aabb::surrounding_box(diagonal1, diagonal2))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        plane::solid_angle_pdf(self, self.area, origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let p = self.q + common::random_double(rng) * self.u + common::random_double(rng) * self.v;
+        p - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    // A 2 x 2 square at y = 1, facing up
+    fn square() -> Quad {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Quad::new(
+            Point3::new(-1.0, 1.0, 1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -2.0),
+            mat,
+        )
+    }
+
+    #[test]
+    fn hit_and_uv() {
+        let quad = square();
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(0.5, 3.0, -0.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(quad.hit(&r, 0.001, common::INFINITY, &mut rec));
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        // Fractions of the way along `u` and `v`
+        assert!((rec.u - 0.75).abs() < 1e-9);
+        assert!((rec.v - 0.75).abs() < 1e-9);
+
+        // From below the back is seen; beside it nothing is
+        let r = Ray::new(Point3::new(-0.5, 0.0, 0.5), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        assert!(quad.hit(&r, 0.001, common::INFINITY, &mut rec));
+        assert!(!rec.front_face);
+        assert!((rec.u - 0.25).abs() < 1e-9 && (rec.v - 0.25).abs() < 1e-9);
+        let r = Ray::new(Point3::new(1.5, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        assert!(!quad.hit(&r, 0.001, common::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn pdf_matches_sampling() {
+        let quad = square();
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        // Straight up: distance 1, facing the origin, over an area of 4
+        assert!((quad.pdf_value(origin, Vec3::new(0.0, 2.0, 0.0)) - 0.25).abs() < 1e-9);
+        // To a corner: the squared distance over the cosine is 3 sqrt(3)
+        let corner = quad.pdf_value(origin, Vec3::new(1.0, 1.0, 1.0) * 0.99999);
+        assert!((corner - 3.0 * f64::sqrt(3.0) / 4.0).abs() < 1e-3);
+        assert_eq!(quad.pdf_value(origin, Vec3::new(0.0, -1.0, 0.0)), 0.0);
+
+        // Sampled directions all point at the quad, and the density
+        // integrates to one over the sphere of directions
+        let mut rng = common::pixel_rng(3, 0);
+        for _ in 0..1000 {
+            assert!(quad.pdf_value(origin, quad.random(origin, &mut rng)) > 0.0);
+        }
+        let n = 200_000;
+        let total: f64 = (0..n)
+            .map(|_| quad.pdf_value(origin, vec3::random_unit_vector(&mut rng)))
+            .sum();
+        let integral = 4.0 * common::PI * total / n as f64;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+    }
+}
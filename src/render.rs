@@ -0,0 +1,494 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::common::{self, Rng};
+use crate::film::Film;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::ray::Ray;
+use crate::vec3;
+
+/// What a ray sees when it leaves the scene without hitting anything
+#[derive(Clone, Copy)]
+pub enum Background {
+    // No light from outside the scene, for scenes lit only by their own lights
+    Black,
+    Solid(Color),
+    // White at the horizon blending to light blue overhead
+    Gradient,
+}
+
+impl Background {
+    pub fn color(&self, r: &Ray) -> Color {
+        match self {
+            Background::Black => Color::new(0.0, 0.0, 0.0),
+            Background::Solid(color) => *color,
+            Background::Gradient => {
+                let unit_direction = vec3::unit_vector(r.direction());
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+            }
+        }
+    }
+}
+
+pub struct RenderSettings {
+    pub image_width: usize,
+    pub image_height: usize,
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    pub background: Background,
+    pub threads: usize,
+    pub tile_size: usize,
+    // Seed for the random number generator; the same seed gives the same image
+    pub seed: u64,
+    // Report progress on stderr while rendering
+    pub progress: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            image_width: 400,
+            image_height: 225,
+            samples_per_pixel: 50,
+            max_depth: 30,
+            background: Background::Gradient,
+            threads: RenderSettings::default_threads(),
+            tile_size: 16,
+            seed: rand::random(),
+            progress: true,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Number of worker threads to use when none is requested explicitly
+    pub fn default_threads() -> usize {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+}
+
+// A rectangular block of pixels, in image coordinates (row 0 is the top row)
+#[derive(Clone, Copy)]
+struct Tile {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+pub fn ray_color(
+    r: &Ray,
+    background: &Background,
+    world: &dyn Hittable,
+    lights: &HittableList,
+    depth: i32,
+    rng: &mut Rng,
+) -> Color {
+    trace(r, background, world, lights, depth, rng).0
+}
+
+// Like `ray_color`, but also report whether the ray hit an object at all
+//
+// At every non-specular bounce light arrives by two routes: a direction
+// sampled towards the lights, and the scattered ray happening to hit a light.
+// Both are kept and weighed against each other with the power heuristic.
+fn trace(
+    r: &Ray,
+    background: &Background,
+    world: &dyn Hittable,
+    lights: &HittableList,
+    depth: i32,
+    rng: &mut Rng,
+) -> (Color, bool) {
+    let mut color = Color::new(0.0, 0.0, 0.0);
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut ray = *r;
+    let mut hit_anything = false;
+    // Density with which the last bounce sampled `ray`, `None` when it came
+    // from the camera or a specular surface and light sampling could not
+    // have found the same light
+    let mut bsdf_pdf: Option<f64> = None;
+
+    // If we've exceeded the ray bounce limit, no more light is gathered
+    for bounce in 0..depth {
+        let mut rec = HitRecord::new();
+        if !world.sample_hit(&ray, 0.001, common::INFINITY, &mut rec, rng) {
+            color += throughput * background.color(&ray);
+            break;
+        }
+        if bounce == 0 {
+            hit_anything = true;
+        }
+
+        let mat = rec.mat.as_ref().unwrap();
+        let emitted = mat.emitted(&ray, &rec);
+        if emitted.length_squared() > 0.0 {
+            let weight = match bsdf_pdf {
+                Some(pdf) => power_heuristic(pdf, lights.pdf_value(ray.origin(), ray.direction())),
+                None => 1.0,
+            };
+            color += weight * throughput * emitted;
+        }
+
+        let Some(srec) = mat.scatter(&ray, &rec, rng) else {
+            break;
+        };
+
+        if srec.is_specular {
+            bsdf_pdf = None;
+        } else {
+            if !lights.objects().is_empty() {
+                color += throughput * sample_light(&ray, &rec, world, lights, rng);
+            }
+            bsdf_pdf = Some(srec.pdf);
+        }
+        throughput = throughput * srec.attenuation;
+        ray = srec.scattered;
+    }
+
+    (color, hit_anything)
+}
+
+// Light reaching a non-specular surface from a direction sampled towards one
+// of the lights, traced with a shadow ray so that occluded lights contribute
+// nothing
+fn sample_light(
+    r_in: &Ray,
+    rec: &HitRecord,
+    world: &dyn Hittable,
+    lights: &HittableList,
+    rng: &mut Rng,
+) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let mat = rec.mat.as_ref().unwrap();
+
+    let shadow_ray = Ray::new(rec.p, lights.random(rec.p, rng), r_in.time());
+    let light_pdf = lights.pdf_value(rec.p, shadow_ray.direction());
+    if light_pdf <= 0.0 {
+        return black;
+    }
+    let f = mat.eval(r_in, rec, &shadow_ray);
+    if f.length_squared() == 0.0 {
+        return black;
+    }
+
+    let mut light_rec = HitRecord::new();
+    if !world.sample_hit(&shadow_ray, 0.001, common::INFINITY, &mut light_rec, rng) {
+        return black;
+    }
+    let emitted = light_rec
+        .mat
+        .as_ref()
+        .unwrap()
+        .emitted(&shadow_ray, &light_rec);
+
+    let weight = power_heuristic(light_pdf, mat.scattering_pdf(r_in, rec, &shadow_ray));
+    weight * f * emitted / light_pdf
+}
+
+// Weight of a sample drawn with density `pdf` when another strategy could
+// have drawn it with density `other_pdf`
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+fn split_tiles(settings: &RenderSettings) -> Vec<Tile> {
+    let size = settings.tile_size.max(1);
+    let mut tiles = Vec::new();
+    for y0 in (0..settings.image_height).step_by(size) {
+        for x0 in (0..settings.image_width).step_by(size) {
+            tiles.push(Tile {
+                x0,
+                y0,
+                x1: usize::min(x0 + size, settings.image_width),
+                y1: usize::min(y0 + size, settings.image_height),
+            });
+        }
+    }
+    tiles
+}
+
+fn render_tile(
+    tile: Tile,
+    world: &dyn Hittable,
+    lights: &HittableList,
+    cam: &dyn Camera,
+    settings: &RenderSettings,
+) -> Vec<(Color, u32)> {
+    let width = settings.image_width;
+    let height = settings.image_height;
+    let mut pixels = Vec::with_capacity((tile.x1 - tile.x0) * (tile.y1 - tile.y0));
+
+    for row in tile.y0..tile.y1 {
+        // The camera's v coordinate grows upwards, image rows grow downwards
+        let j = height - 1 - row;
+        for i in tile.x0..tile.x1 {
+            let mut rng = common::pixel_rng(settings.seed, (row * width + i) as u64);
+            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+            let mut hits = 0u32;
+            for _ in 0..settings.samples_per_pixel {
+                let u = (i as f64 + common::random_double(&mut rng)) / width as f64;
+                let v = (j as f64 + common::random_double(&mut rng)) / height as f64;
+                // Parts of the image the camera cannot see stay black
+                let Some(r) = cam.get_ray(u, v, &mut rng) else {
+                    continue;
+                };
+                let (sample, hit) = trace(
+                    &r,
+                    &settings.background,
+                    world,
+                    lights,
+                    settings.max_depth,
+                    &mut rng,
+                );
+                pixel_color += sample;
+                hits += hit as u32;
+            }
+            pixels.push((pixel_color, hits));
+        }
+    }
+
+    pixels
+}
+
+/// Render the world on a pool of worker threads
+///
+/// The image is split into square tiles which the workers pull from a shared
+/// queue and the finished tiles are gathered on a film. `lights` holds the
+/// emitters that diffuse surfaces sample directly; they must also be part of
+/// `world`.
+pub fn render(
+    world: &dyn Hittable,
+    lights: &HittableList,
+    cam: &dyn Camera,
+    settings: &RenderSettings,
+) -> Film {
+    let width = settings.image_width;
+    let tiles = split_tiles(settings);
+    let next_tile = AtomicUsize::new(0);
+    let tiles_done = AtomicUsize::new(0);
+    let threads = settings.threads.clamp(1, tiles.len().max(1));
+
+    let results: Vec<(Tile, Vec<(Color, u32)>)> = thread::scope(|s| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut finished = Vec::new();
+                    loop {
+                        let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                        if index >= tiles.len() {
+                            break;
+                        }
+                        let tile = tiles[index];
+                        finished.push((tile, render_tile(tile, world, lights, cam, settings)));
+
+                        let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+                        if settings.progress {
+                            eprint!("\rTiles remaining: {} ", tiles.len() - done);
+                        }
+                    }
+                    finished
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("render worker panicked"))
+            .collect()
+    });
+
+    let samples = settings.samples_per_pixel as u32;
+    let mut film = Film::new(width, settings.image_height);
+    for (tile, pixels) in results {
+        let tile_width = tile.x1 - tile.x0;
+        for (k, &(color_sum, hits)) in pixels.iter().enumerate() {
+            let (x, y) = (tile.x0 + k % tile_width, tile.y0 + k / tile_width);
+            film.add_samples(x, y, color_sum, hits, samples);
+        }
+    }
+
+    film
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::bvh::BvhNode;
+    use crate::material::{DiffuseLight, Lambertian, Metal};
+    use crate::quad::Quad;
+    use crate::scene;
+    use crate::sphere::Sphere;
+    use crate::vec3::{Point3, Vec3};
+
+    // Every channel of every pixel, to compare images bit for bit
+    fn channels(film: &Film) -> Vec<f64> {
+        let mut values = Vec::new();
+        for y in 0..film.height() {
+            for x in 0..film.width() {
+                let c = film.pixel(x, y);
+                values.extend([c.x(), c.y(), c.z(), film.alpha(x, y)]);
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn same_seed_same_image_at_any_thread_count() {
+        // Lights, glass and participating media all draw random numbers
+        let scene = scene::parse(include_str!("../scenes/fog.toml")).unwrap();
+        let world = BvhNode::new(scene.world);
+        let cam = scene.camera.build(48.0 / 27.0);
+        let render_with = |threads: usize, seed: u64| {
+            let settings = RenderSettings {
+                image_width: 48,
+                image_height: 27,
+                samples_per_pixel: 4,
+                max_depth: 10,
+                threads,
+                tile_size: 8,
+                seed,
+                progress: false,
+                ..scene.settings
+            };
+            channels(&render(&world, &scene.lights, cam.as_ref(), &settings))
+        };
+
+        let single = render_with(1, 42);
+        assert_eq!(render_with(4, 42), single);
+        assert_eq!(render_with(7, 42), single);
+        assert_ne!(render_with(4, 43), single);
+    }
+
+    // Average red radiance along `r` over many samples
+    fn mean_radiance(r: &Ray, world: &HittableList, lights: &HittableList, n: usize) -> f64 {
+        let mut rng = common::pixel_rng(11, 0);
+        let black = Background::Black;
+        let sum: f64 = (0..n)
+            .map(|_| ray_color(r, &black, world, lights, 10, &mut rng).x())
+            .sum();
+        sum / n as f64
+    }
+
+    // A large white diffuse floor at y = 0 with albedo 0.5, seen at the origin
+    fn lit_floor(light: Arc<dyn Hittable>) -> (Ray, HittableList, HittableList) {
+        let white = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Quad::new(
+            Point3::new(-50.0, 0.0, -50.0),
+            Vec3::new(0.0, 0.0, 100.0),
+            Vec3::new(100.0, 0.0, 0.0),
+            white,
+        )));
+        world.add(light.clone());
+        let mut lights = HittableList::new();
+        lights.add(light);
+        let r = Ray::new(Point3::new(0.0, 2.0, -3.0), Vec3::new(0.0, -2.0, 3.0), 0.0);
+        (r, world, lights)
+    }
+
+    fn light(radiance: f64) -> Arc<DiffuseLight> {
+        Arc::new(DiffuseLight::new(Color::new(radiance, radiance, radiance)))
+    }
+
+    #[test]
+    fn diffuse_floor_matches_analytic_irradiance() {
+        // A sphere of radius 1 whose center is 4 above the floor subtends
+        // sin^2 = 1/16 of the sky, so the floor reflects 0.5 * 10 / 16
+        let sphere = Arc::new(Sphere::new(Point3::new(0.0, 4.0, 0.0), 1.0, light(10.0)));
+        let (r, world, lights) = lit_floor(sphere);
+        let expected = 0.5 * 10.0 / 16.0;
+        // With light sampling and MIS, and with scattered rays alone
+        let mis = mean_radiance(&r, &world, &lights, 20_000);
+        assert!(
+            (mis / expected - 1.0).abs() < 0.02,
+            "{} != {}",
+            mis,
+            expected
+        );
+        let bsdf = mean_radiance(&r, &world, &HittableList::new(), 200_000);
+        assert!(
+            (bsdf / expected - 1.0).abs() < 0.03,
+            "{} != {}",
+            bsdf,
+            expected
+        );
+
+        // A 2 x 2 square light 4 above the floor; the form factor to each
+        // quarter of it is that of a rectangle with a corner over the origin
+        let square = Arc::new(Quad::new(
+            Point3::new(-1.0, 4.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0),
+            light(10.0),
+        ));
+        let (r, world, lights) = lit_floor(square);
+        let x: f64 = 0.25;
+        let corner = x / (1.0 + x * x).sqrt() * (x / (1.0 + x * x).sqrt()).atan() / common::PI;
+        let expected = 0.5 * 10.0 * 4.0 * corner;
+        let mis = mean_radiance(&r, &world, &lights, 20_000);
+        assert!(
+            (mis / expected - 1.0).abs() < 0.02,
+            "{} != {}",
+            mis,
+            expected
+        );
+        let bsdf = mean_radiance(&r, &world, &HittableList::new(), 200_000);
+        assert!(
+            (bsdf / expected - 1.0).abs() < 0.03,
+            "{} != {}",
+            bsdf,
+            expected
+        );
+    }
+
+    #[test]
+    fn furnace() {
+        // A convex diffuse object under a uniform sky reflects exactly its
+        // albedo, as every bounce escapes
+        let grey = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, grey)));
+        let sky = Background::Solid(Color::new(1.0, 1.0, 1.0));
+        let mut rng = common::pixel_rng(12, 0);
+        for origin in [Point3::new(0.0, 0.0, 5.0), Point3::new(0.9, 0.3, -3.0)] {
+            let r = Ray::new(origin, -origin, 0.0);
+            for _ in 0..100 {
+                let c = ray_color(&r, &sky, &world, &HittableList::new(), 10, &mut rng);
+                assert!((c.x() - 0.5).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn lights_seen_in_mirrors_are_not_down_weighted() {
+        // Light sampling cannot find a light through a mirror, so the mirror
+        // shows the light at full brightness
+        let mirror = Arc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0));
+        let sphere = Arc::new(Sphere::new(Point3::new(4.0, 4.0, 0.0), 1.0, light(10.0)));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Quad::new(
+            Point3::new(-50.0, 0.0, -50.0),
+            Vec3::new(0.0, 0.0, 100.0),
+            Vec3::new(100.0, 0.0, 0.0),
+            mirror,
+        )));
+        world.add(sphere.clone());
+        let mut lights = HittableList::new();
+        lights.add(sphere);
+        let r = Ray::new(Point3::new(-4.0, 4.0, 0.0), Vec3::new(1.0, -1.0, 0.0), 0.0);
+        let mut rng = common::pixel_rng(13, 0);
+        let c = ray_color(&r, &Background::Black, &world, &lights, 10, &mut rng);
+        assert!((c.x() - 10.0).abs() < 1e-9, "{}", c);
+    }
+}
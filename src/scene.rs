@@ -0,0 +1,1779 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::animation::{CameraAnimation, CameraKeyframe, Interpolation};
+use crate::annulus::Annulus;
+use crate::bvh::BvhNode;
+use crate::camera::{CameraParams, FisheyeMapping, Projection};
+use crate::capsule::Capsule;
+use crate::cone::Cone;
+use crate::csg::{Csg, CsgOp};
+use crate::cube::Cube;
+use crate::cylinder::{Caps, Cylinder};
+use crate::disk::Disk;
+use crate::fractal::{self, Mandelbulb, MengerSponge, QuaternionJulia};
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::material::{
+    Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal, OrbitTrap,
+};
+use crate::medium::{ConstantMedium, GridVolume};
+use crate::obj;
+use crate::plane::Plane;
+use crate::quad::Quad;
+use crate::render::{Background, RenderSettings};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::stereo::StereoLayout;
+use crate::torus::Torus;
+use crate::transform::{AnimatedInstance, Instance, Keyframe, Mat4, Quaternion};
+use crate::triangle::Triangle;
+use crate::vec3::{Point3, Vec3};
+use crate::voxel;
+
+// Scene files use a small subset of TOML:
+//
+//   # comment
+//   [render]                 table
+//   image_width = 400        number
+//   [materials.red]          named material
+//   [solids.hole]            named solid, for CSG objects and media to use
+//   type = "lambertian"      string
+//   albedo = [0.8, 0.1, 0.1] array
+//   [[objects]]              one entry per object
+//   [[keyframes]]            one entry per camera keyframe
+//
+// Values may be numbers, strings or single-line arrays.
+
+/// A parsed scene, ready to render
+pub struct Scene {
+    pub world: HittableList,
+    // Light shapes that can be sampled directly, also part of `world`
+    pub lights: HittableList,
+    pub camera: CameraParams,
+    // Movement of the camera over a sequence of frames, if it moves
+    pub animation: Option<CameraAnimation>,
+    pub settings: RenderSettings,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        field: Option<String>,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "cannot read scene: {}", err),
+            SceneError::Parse {
+                line,
+                field: Some(field),
+                message,
+            } => write!(f, "line {}: field `{}`: {}", line, field, message),
+            SceneError::Parse {
+                line,
+                field: None,
+                message,
+            } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(err: io::Error) -> SceneError {
+        SceneError::Io(err)
+    }
+}
+
+fn error(line: usize, field: Option<&str>, message: impl Into<String>) -> SceneError {
+    SceneError::Parse {
+        line,
+        field: field.map(str::to_string),
+        message: message.into(),
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Number(f64),
+    Str(String),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "a number",
+            Value::Str(_) => "a string",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+struct Field {
+    key: String,
+    value: Value,
+    line: usize,
+}
+
+// A `[table]` or one `[[array]]` entry together with its fields
+struct Section {
+    name: String,
+    line: usize,
+    array: bool,
+    fields: Vec<Field>,
+}
+
+impl Section {
+    fn get(&self, key: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.key == key)
+    }
+
+    // Reject fields that are not in `allowed`, so that typos do not go unnoticed
+    fn check_keys(&self, allowed: &[&str]) -> Result<(), SceneError> {
+        match self
+            .fields
+            .iter()
+            .find(|f| !allowed.contains(&f.key.as_str()))
+        {
+            Some(f) => Err(error(
+                f.line,
+                Some(&f.key),
+                format!("unknown field in [{}]", self.name),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn require(&self, key: &str) -> Result<&Field, SceneError> {
+        self.get(key).ok_or_else(|| {
+            error(
+                self.line,
+                Some(key),
+                format!("missing required field in [{}]", self.name),
+            )
+        })
+    }
+
+    fn number(&self, key: &str) -> Result<f64, SceneError> {
+        to_number(self.require(key)?)
+    }
+
+    fn number_or(&self, key: &str, default: f64) -> Result<f64, SceneError> {
+        self.get(key).map_or(Ok(default), to_number)
+    }
+
+    fn vec3(&self, key: &str) -> Result<Vec3, SceneError> {
+        to_vec3(self.require(key)?)
+    }
+
+    fn vec3_or(&self, key: &str, default: Vec3) -> Result<Vec3, SceneError> {
+        self.get(key).map_or(Ok(default), to_vec3)
+    }
+
+    fn direction(&self, key: &str) -> Result<Vec3, SceneError> {
+        to_direction(self.require(key)?)
+    }
+
+    fn direction_or(&self, key: &str, default: Vec3) -> Result<Vec3, SceneError> {
+        self.get(key).map_or(Ok(default), to_direction)
+    }
+
+    fn quaternion(&self, key: &str) -> Result<[f64; 4], SceneError> {
+        let field = self.require(key)?;
+        if let Value::Array(items) = &field.value {
+            if let [Value::Number(a), Value::Number(b), Value::Number(c), Value::Number(d)] =
+                items.as_slice()
+            {
+                return Ok([*a, *b, *c, *d]);
+            }
+        }
+        Err(error(
+            field.line,
+            Some(&field.key),
+            "expected an array of four numbers",
+        ))
+    }
+
+    fn string(&self, key: &str) -> Result<&str, SceneError> {
+        let field = self.require(key)?;
+        match &field.value {
+            Value::Str(s) => Ok(s),
+            other => Err(type_error(field, "a string", other)),
+        }
+    }
+
+    fn integer(&self, key: &str) -> Result<usize, SceneError> {
+        let field = self.require(key)?;
+        let n = to_number(field)?;
+        if n < 1.0 || n.fract() != 0.0 {
+            return Err(error(
+                field.line,
+                Some(&field.key),
+                "expected a positive integer",
+            ));
+        }
+        Ok(n as usize)
+    }
+
+    fn integer_or(&self, key: &str, default: usize) -> Result<usize, SceneError> {
+        match self.get(key) {
+            Some(_) => self.integer(key),
+            None => Ok(default),
+        }
+    }
+}
+
+fn type_error(field: &Field, expected: &str, found: &Value) -> SceneError {
+    error(
+        field.line,
+        Some(&field.key),
+        format!("expected {}, found {}", expected, found.type_name()),
+    )
+}
+
+fn to_number(field: &Field) -> Result<f64, SceneError> {
+    match field.value {
+        Value::Number(n) => Ok(n),
+        ref other => Err(type_error(field, "a number", other)),
+    }
+}
+
+fn to_vec3(field: &Field) -> Result<Vec3, SceneError> {
+    if let Value::Array(items) = &field.value {
+        if let [Value::Number(x), Value::Number(y), Value::Number(z)] = items.as_slice() {
+            return Ok(Vec3::new(*x, *y, *z));
+        }
+    }
+    Err(error(
+        field.line,
+        Some(&field.key),
+        "expected an array of three numbers",
+    ))
+}
+
+// A vector giving a direction, so not zero
+fn to_direction(field: &Field) -> Result<Vec3, SceneError> {
+    let v = to_vec3(field)?;
+    if v.length_squared() == 0.0 {
+        return Err(error(field.line, Some(&field.key), "must not be zero"));
+    }
+    Ok(v)
+}
+
+// Parsing
+
+fn parse_sections(text: &str) -> Result<Vec<Section>, SceneError> {
+    let mut sections = vec![Section {
+        name: String::new(),
+        line: 1,
+        array: false,
+        fields: Vec::new(),
+    }];
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = strip_comment(raw).trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = content.strip_prefix('[') {
+            let (name, array) = match header.strip_prefix('[') {
+                Some(inner) => (inner.strip_suffix("]]"), true),
+                None => (header.strip_suffix(']'), false),
+            };
+            let name = name
+                .map(str::trim)
+                .filter(|n| is_valid_name(n))
+                .ok_or_else(|| {
+                    error(line, None, format!("malformed table header `{}`", content))
+                })?;
+            if let Some(previous) = sections.iter().find(|s| s.name == name) {
+                if !array || !previous.array {
+                    return Err(error(line, None, format!("table [{}] defined twice", name)));
+                }
+            }
+            sections.push(Section {
+                name: name.to_string(),
+                line,
+                array,
+                fields: Vec::new(),
+            });
+            continue;
+        }
+
+        let (key, value) = content
+            .split_once('=')
+            .ok_or_else(|| error(line, None, "expected `key = value`"))?;
+        let key = key.trim();
+        if !is_valid_name(key) || key.contains('.') {
+            return Err(error(line, None, format!("invalid key `{}`", key)));
+        }
+
+        let mut chars = value.trim().chars().peekable();
+        let value = parse_value(&mut chars).map_err(|msg| error(line, Some(key), msg))?;
+        if chars.next().is_some() {
+            return Err(error(line, Some(key), "unexpected characters after value"));
+        }
+
+        let section = sections.last_mut().unwrap();
+        if section.get(key).is_some() {
+            return Err(error(line, Some(key), "field defined twice"));
+        }
+        section.fields.push(Field {
+            key: key.to_string(),
+            value,
+            line,
+        });
+    }
+
+    Ok(sections)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        })
+}
+
+// Cut a line at the first `#` that is not inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_value(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Value, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        None => Err("missing value".to_string()),
+        Some('"') => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    None => return Err("unterminated string".to_string()),
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => s.push('\n'),
+                        Some('t') => s.push('\t'),
+                        Some('"') => s.push('"'),
+                        Some('\\') => s.push('\\'),
+                        _ => return Err("invalid escape sequence".to_string()),
+                    },
+                    Some(c) => s.push(c),
+                }
+            }
+            Ok(Value::Str(s))
+        }
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                skip_whitespace(chars);
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    break;
+                }
+                items.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => break,
+                    _ => return Err("expected `,` or `]` in array".to_string()),
+                }
+            }
+            Ok(Value::Array(items))
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ',' || c == ']' || c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            word.parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(Value::Number)
+                .ok_or_else(|| format!("invalid value `{}`", word))
+        }
+    }
+}
+
+// Scene construction
+
+/// Load a scene from a file on disk
+///
+/// Files the scene refers to, such as meshes, are found relative to the
+/// scene file's directory.
+pub fn load(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    parse_in(&text, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Build a scene from the text of a scene file
+///
+/// Files the scene refers to are found relative to the current directory.
+pub fn parse(text: &str) -> Result<Scene, SceneError> {
+    parse_in(text, Path::new(""))
+}
+
+fn parse_in(text: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    let sections = parse_sections(text)?;
+
+    let mut render = None;
+    let mut camera = None;
+    let mut animation = None;
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut emitters = HashSet::new();
+    let mut meshes = HashMap::new();
+    let mut solid_sections = Vec::new();
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    for section in &sections {
+        let array_name = matches!(section.name.as_str(), "objects" | "keyframes");
+        if section.array != array_name {
+            let message = if section.array {
+                format!("[[{}]] is not an array of tables", section.name)
+            } else {
+                format!("{0} must be declared as [[{0}]]", section.name)
+            };
+            return Err(error(section.line, None, message));
+        }
+
+        match section.name.as_str() {
+            "" => {
+                if let Some(field) = section.fields.first() {
+                    return Err(error(
+                        field.line,
+                        Some(&field.key),
+                        "field outside of any table",
+                    ));
+                }
+            }
+            "render" => render = Some(section),
+            "camera" => camera = Some(section),
+            "animation" => animation = Some(section),
+            "objects" | "keyframes" => {}
+            name => {
+                if let Some(material_name) = name.strip_prefix("materials.") {
+                    materials.insert(material_name.to_string(), build_material(section)?);
+                    if section.string("type")? == "diffuse_light" {
+                        emitters.insert(material_name);
+                    }
+                } else if let Some(solid_name) = name.strip_prefix("solids.") {
+                    solid_sections.push((solid_name, section));
+                } else {
+                    return Err(error(
+                        section.line,
+                        None,
+                        format!("unknown table [{}]", name),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Solids are not rendered themselves, only combined by CSG objects; each
+    // can use the solids declared before it
+    let mut solids = HashMap::new();
+    for (name, section) in solid_sections {
+        let solid = build_object(section, &materials, &solids, base_dir, &mut meshes)?;
+        let solid = place(solid, build_transform(section)?);
+        solids.insert(name.to_string(), animate(solid, build_motion(section)?));
+    }
+
+    // Objects are built last so they can refer to materials declared anywhere
+    for section in sections.iter().filter(|s| s.name == "objects") {
+        let object = build_object(section, &materials, &solids, base_dir, &mut meshes)?;
+        let transform = build_transform(section)?;
+        let motion = build_motion(section)?;
+        let still = transform.is_none() && motion.is_none();
+        let object = animate(place(object, transform), motion);
+        // Only these shapes know how to sample themselves as lights
+        if still
+            && matches!(
+                section.string("type")?,
+                "sphere" | "quad" | "disk" | "annulus" | "triangle"
+            )
+            && emitters.contains(section.string("material")?)
+        {
+            lights.add(object.clone());
+        }
+        world.add(object);
+    }
+
+    let settings = build_settings(render)?;
+    let camera = build_camera(camera)?;
+    let keyframes: Vec<&Section> = sections.iter().filter(|s| s.name == "keyframes").collect();
+    let animation = build_animation(animation, &keyframes, &camera)?;
+
+    Ok(Scene {
+        world,
+        lights,
+        camera,
+        animation,
+        settings,
+    })
+}
+
+fn build_settings(section: Option<&Section>) -> Result<RenderSettings, SceneError> {
+    let mut settings = RenderSettings::default();
+    let Some(section) = section else {
+        return Ok(settings);
+    };
+
+    section.check_keys(&[
+        "image_width",
+        "image_height",
+        "aspect_ratio",
+        "samples_per_pixel",
+        "max_depth",
+        "background",
+    ])?;
+
+    if section.get("image_height").is_some() && section.get("aspect_ratio").is_some() {
+        return Err(error(
+            section.require("aspect_ratio")?.line,
+            Some("aspect_ratio"),
+            "give either image_height or aspect_ratio, not both",
+        ));
+    }
+
+    if section.get("image_width").is_some() {
+        settings.image_width = section.integer("image_width")?;
+    }
+    settings.image_height = if section.get("image_height").is_some() {
+        section.integer("image_height")?
+    } else {
+        let aspect_ratio = section.number_or("aspect_ratio", 16.0 / 9.0)?;
+        if aspect_ratio <= 0.0 {
+            return Err(error(
+                section.require("aspect_ratio")?.line,
+                Some("aspect_ratio"),
+                "must be positive",
+            ));
+        }
+        usize::max((settings.image_width as f64 / aspect_ratio) as usize, 1)
+    };
+    if section.get("samples_per_pixel").is_some() {
+        settings.samples_per_pixel = section.integer("samples_per_pixel")? as i32;
+    }
+    if section.get("max_depth").is_some() {
+        settings.max_depth = section.integer("max_depth")? as i32;
+    }
+    if let Some(field) = section.get("background") {
+        settings.background = to_background(field)?;
+    }
+
+    Ok(settings)
+}
+
+// `"gradient"`, `"black"` or a constant color given as an array
+fn to_background(field: &Field) -> Result<Background, SceneError> {
+    match &field.value {
+        Value::Str(name) if name == "gradient" => Ok(Background::Gradient),
+        Value::Str(name) if name == "black" => Ok(Background::Black),
+        Value::Str(name) => Err(error(
+            field.line,
+            Some(&field.key),
+            format!("unknown background `{}`", name),
+        )),
+        _ => Ok(Background::Solid(to_vec3(field)?)),
+    }
+}
+
+const CAMERA_KEYS: &[&str] = &[
+    "projection",
+    "lookfrom",
+    "lookat",
+    "vup",
+    "shutter_open",
+    "shutter_close",
+];
+
+fn build_camera(section: Option<&Section>) -> Result<CameraParams, SceneError> {
+    let section = section.ok_or_else(|| error(1, None, "missing [camera] table"))?;
+    let projection = match section.get("projection") {
+        Some(_) => section.string("projection")?,
+        None => "perspective",
+    };
+    let extra_keys: &[&str] = match projection {
+        "perspective" => &[
+            "vfov",
+            "aperture",
+            "focus_dist",
+            "blades",
+            "blade_rotation",
+            "stereo",
+            "interocular",
+            "convergence",
+        ],
+        "orthographic" => &["height"],
+        "fisheye" => &["fov", "mapping", "stereo", "interocular"],
+        "equirectangular" => &["stereo", "interocular"],
+        other => {
+            return Err(error(
+                section.require("projection")?.line,
+                Some("projection"),
+                format!(
+                    "unknown projection `{}` (expected \"perspective\", \"orthographic\", \
+                     \"fisheye\" or \"equirectangular\")",
+                    other
+                ),
+            ))
+        }
+    };
+    section.check_keys(&[CAMERA_KEYS, extra_keys].concat())?;
+
+    let aperture = section.number_or("aperture", 0.0)?;
+    if aperture < 0.0 {
+        return Err(error(
+            section.require("aperture")?.line,
+            Some("aperture"),
+            "must not be negative",
+        ));
+    }
+    let focus_dist = match section.get("focus_dist") {
+        Some(field) => {
+            let distance = to_number(field)?;
+            if distance <= 0.0 {
+                return Err(error(field.line, Some("focus_dist"), "must be positive"));
+            }
+            Some(distance)
+        }
+        None => None,
+    };
+
+    let shutter_open = section.number_or("shutter_open", 0.0)?;
+    let shutter_close = section.number_or("shutter_close", shutter_open)?;
+    if shutter_close < shutter_open {
+        return Err(error(
+            section.require("shutter_close")?.line,
+            Some("shutter_close"),
+            "must not be before shutter_open",
+        ));
+    }
+
+    let stereo = match section.get("stereo") {
+        None => None,
+        Some(field) => match section.string("stereo")? {
+            "side_by_side" => Some(StereoLayout::SideBySide),
+            "top_bottom" => Some(StereoLayout::TopBottom),
+            other => {
+                return Err(error(
+                    field.line,
+                    Some("stereo"),
+                    format!(
+                        "unknown stereo layout `{}` \
+                         (expected \"side_by_side\" or \"top_bottom\")",
+                        other
+                    ),
+                ))
+            }
+        },
+    };
+    let interocular = section.number_or("interocular", 0.065)?;
+    if interocular < 0.0 {
+        return Err(error(
+            section.require("interocular")?.line,
+            Some("interocular"),
+            "must not be negative",
+        ));
+    }
+    let convergence = match section.get("convergence") {
+        Some(field) => {
+            let distance = to_number(field)?;
+            if distance <= 0.0 {
+                return Err(error(field.line, Some("convergence"), "must be positive"));
+            }
+            Some(distance)
+        }
+        None => None,
+    };
+
+    let (projection, vfov) = match projection {
+        "orthographic" => {
+            let height = section.number("height")?;
+            if height <= 0.0 {
+                return Err(error(
+                    section.require("height")?.line,
+                    Some("height"),
+                    "must be positive",
+                ));
+            }
+            (Projection::Orthographic { height }, 0.0)
+        }
+        "fisheye" => {
+            let fov = section.number_or("fov", 180.0)?;
+            if fov <= 0.0 || fov > 360.0 {
+                return Err(error(
+                    section.require("fov")?.line,
+                    Some("fov"),
+                    "must be more than 0 and at most 360",
+                ));
+            }
+            let mapping = match section.get("mapping") {
+                None => FisheyeMapping::Equidistant,
+                Some(field) => match section.string("mapping")? {
+                    "equidistant" => FisheyeMapping::Equidistant,
+                    "equisolid" => FisheyeMapping::Equisolid,
+                    other => {
+                        return Err(error(
+                            field.line,
+                            Some("mapping"),
+                            format!(
+                                "unknown mapping `{}` \
+                                 (expected \"equidistant\" or \"equisolid\")",
+                                other
+                            ),
+                        ))
+                    }
+                },
+            };
+            (Projection::Fisheye { fov, mapping }, 0.0)
+        }
+        "equirectangular" => (Projection::Equirectangular, 0.0),
+        _ => (Projection::Perspective, section.number("vfov")?),
+    };
+
+    Ok(CameraParams {
+        projection,
+        lookfrom: section.vec3("lookfrom")?,
+        lookat: section.vec3("lookat")?,
+        vup: section.vec3_or("vup", Vec3::new(0.0, 1.0, 0.0))?,
+        vfov,
+        aperture,
+        focus_dist,
+        blades: section.integer_or("blades", 0)?,
+        blade_rotation: section.number_or("blade_rotation", 0.0)?,
+        shutter_open,
+        shutter_close,
+        stereo,
+        interocular,
+        convergence,
+    })
+}
+
+fn build_animation(
+    section: Option<&Section>,
+    keyframes: &[&Section],
+    camera: &CameraParams,
+) -> Result<Option<CameraAnimation>, SceneError> {
+    if let Some(section) = section {
+        section.check_keys(&["interpolation", "orbit", "frames"])?;
+        if section.get("orbit").is_some() {
+            if let Some(key) = keyframes.first() {
+                return Err(error(
+                    key.line,
+                    None,
+                    "an orbit and [[keyframes]] cannot be used together",
+                ));
+            }
+            return Ok(Some(CameraAnimation::orbit(
+                camera.lookfrom,
+                camera.lookat,
+                camera.vup,
+                camera.vfov,
+                section.number("orbit")?,
+                section.integer("frames")?,
+            )));
+        }
+        if section.get("frames").is_some() {
+            return Err(error(
+                section.require("frames")?.line,
+                Some("frames"),
+                "only used with orbit",
+            ));
+        }
+    }
+    if keyframes.is_empty() {
+        return match section {
+            Some(section) => Err(error(
+                section.line,
+                None,
+                "[animation] needs an orbit or [[keyframes]]",
+            )),
+            None => Ok(None),
+        };
+    }
+
+    let interpolation = match section.filter(|s| s.get("interpolation").is_some()) {
+        None => Interpolation::Linear,
+        Some(section) => match section.string("interpolation")? {
+            "linear" => Interpolation::Linear,
+            "catmull_rom" => Interpolation::CatmullRom,
+            other => {
+                return Err(error(
+                    section.require("interpolation")?.line,
+                    Some("interpolation"),
+                    format!(
+                        "unknown interpolation `{}` \
+                         (expected \"linear\" or \"catmull_rom\")",
+                        other
+                    ),
+                ))
+            }
+        },
+    };
+
+    // Keyframes take whatever they leave out from [camera]
+    let keyframes = keyframes
+        .iter()
+        .map(|key| {
+            key.check_keys(&["frame", "lookfrom", "lookat", "vfov", "focus_dist"])?;
+            let lookfrom = key.vec3_or("lookfrom", camera.lookfrom)?;
+            let lookat = key.vec3_or("lookat", camera.lookat)?;
+            let focus_dist = match camera.focus_dist {
+                Some(distance) => key.number_or("focus_dist", distance)?,
+                None => key.number_or("focus_dist", (lookfrom - lookat).length())?,
+            };
+            if focus_dist <= 0.0 {
+                return Err(error(
+                    key.require("focus_dist")?.line,
+                    Some("focus_dist"),
+                    "must be positive",
+                ));
+            }
+            Ok(CameraKeyframe::new(
+                key.number("frame")?,
+                lookfrom,
+                lookat,
+                key.number_or("vfov", camera.vfov)?,
+                focus_dist,
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(CameraAnimation::new(keyframes, interpolation)))
+}
+
+fn build_material(section: &Section) -> Result<Arc<dyn Material>, SceneError> {
+    let kind = section.string("type")?;
+    match kind {
+        "lambertian" => {
+            section.check_keys(&["type", "albedo"])?;
+            Ok(Arc::new(Lambertian::new(section.vec3("albedo")?)))
+        }
+        "metal" => {
+            section.check_keys(&["type", "albedo", "fuzz"])?;
+            Ok(Arc::new(Metal::new(
+                section.vec3("albedo")?,
+                section.number_or("fuzz", 0.0)?,
+            )))
+        }
+        "dielectric" => {
+            section.check_keys(&["type", "index_of_refraction"])?;
+            Ok(Arc::new(Dielectric::new(
+                section.number("index_of_refraction")?,
+            )))
+        }
+        "diffuse_light" => {
+            section.check_keys(&["type", "emit"])?;
+            Ok(Arc::new(DiffuseLight::new(section.vec3("emit")?)))
+        }
+        "orbit_trap" => {
+            section.check_keys(&["type", "low", "high"])?;
+            Ok(Arc::new(OrbitTrap::new(
+                section.vec3("low")?,
+                section.vec3("high")?,
+            )))
+        }
+        "isotropic" => {
+            section.check_keys(&["type", "albedo"])?;
+            Ok(Arc::new(Isotropic::new(section.vec3("albedo")?)))
+        }
+        "henyey_greenstein" => {
+            section.check_keys(&["type", "albedo", "g"])?;
+            Ok(Arc::new(HenyeyGreenstein::new(
+                section.vec3("albedo")?,
+                section.number("g")?,
+            )))
+        }
+        _ => Err(error(
+            section.require("type")?.line,
+            Some("type"),
+            format!("unknown material type `{}`", kind),
+        )),
+    }
+}
+
+fn lookup_material(
+    section: &Section,
+    materials: &HashMap<String, Arc<dyn Material>>,
+) -> Result<Arc<dyn Material>, SceneError> {
+    let field = section.require("material")?;
+    let name = section.string("material")?;
+    materials.get(name).cloned().ok_or_else(|| {
+        error(
+            field.line,
+            Some("material"),
+            format!("no material named `{}`", name),
+        )
+    })
+}
+
+fn lookup_solid(
+    section: &Section,
+    key: &str,
+    solids: &HashMap<String, Arc<dyn Hittable>>,
+) -> Result<Arc<dyn Hittable>, SceneError> {
+    let field = section.require(key)?;
+    let name = section.string(key)?;
+    solids.get(name).cloned().ok_or_else(|| {
+        error(
+            field.line,
+            Some(key),
+            format!("no solid named `{}` declared before this", name),
+        )
+    })
+}
+
+fn build_object(
+    section: &Section,
+    materials: &HashMap<String, Arc<dyn Material>>,
+    solids: &HashMap<String, Arc<dyn Hittable>>,
+    base_dir: &Path,
+    meshes: &mut HashMap<(PathBuf, String), Arc<dyn Hittable>>,
+) -> Result<Arc<dyn Hittable>, SceneError> {
+    let kind = section.string("type")?;
+    let object: Arc<dyn Hittable> = match kind {
+        "sphere" => {
+            check_object_keys(section, &["type", "material", "center", "radius"])?;
+            Arc::new(Sphere::new(
+                section.vec3("center")?,
+                section.number("radius")?,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "moving_sphere" => {
+            check_object_keys(
+                section,
+                &[
+                    "type", "material", "center0", "center1", "time0", "time1", "radius",
+                ],
+            )?;
+            Arc::new(MovingSphere::new(
+                section.vec3("center0")?,
+                section.vec3("center1")?,
+                section.number_or("time0", 0.0)?,
+                section.number_or("time1", 1.0)?,
+                section.number("radius")?,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "cube" => {
+            check_object_keys(section, &["type", "material", "min", "max"])?;
+            Arc::new(Cube::new(
+                section.vec3("min")?,
+                section.vec3("max")?,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "cylinder" => {
+            check_object_keys(
+                section,
+                &[
+                    "type", "material", "center", "height", "base", "top", "radius", "caps",
+                ],
+            )?;
+            let mat = lookup_material(section, materials)?;
+            let radius = section.number("radius")?;
+            let caps = caps_or_both(section)?;
+            if section.get("center").is_some() || section.get("height").is_some() {
+                // Upright cylinder standing on `center`
+                if let Some(field) = section.get("base").or(section.get("top")) {
+                    return Err(error(
+                        field.line,
+                        Some(&field.key),
+                        "give either `center` and `height` or `base` and `top`",
+                    ));
+                }
+                let center = section.vec3("center")?;
+                let height = section.number("height")?;
+                if height == 0.0 {
+                    return Err(error(
+                        section.require("height")?.line,
+                        Some("height"),
+                        "must not be zero",
+                    ));
+                }
+                let top = center + Vec3::new(0.0, height.abs(), 0.0);
+                Arc::new(Cylinder::between(center, top, radius, caps, mat))
+            } else {
+                let (base, top) = base_and_top(section)?;
+                Arc::new(Cylinder::between(base, top, radius, caps, mat))
+            }
+        }
+        "cone" => {
+            check_object_keys(
+                section,
+                &[
+                    "type",
+                    "material",
+                    "base",
+                    "top",
+                    "base_radius",
+                    "top_radius",
+                    "caps",
+                ],
+            )?;
+            let (base, top) = base_and_top(section)?;
+            Arc::new(Cone::new(
+                base,
+                top,
+                section.number("base_radius")?,
+                section.number_or("top_radius", 0.0)?,
+                caps_or_both(section)?,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "capsule" => {
+            check_object_keys(section, &["type", "material", "base", "top", "radius"])?;
+            Arc::new(Capsule::new(
+                section.vec3("base")?,
+                section.vec3("top")?,
+                section.number("radius")?,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "torus" => {
+            check_object_keys(
+                section,
+                &[
+                    "type",
+                    "material",
+                    "center",
+                    "axis",
+                    "major_radius",
+                    "minor_radius",
+                ],
+            )?;
+            Arc::new(Torus::new(
+                section.vec3("center")?,
+                section.direction_or("axis", Vec3::new(0.0, 1.0, 0.0))?,
+                section.number("major_radius")?,
+                section.number("minor_radius")?,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "plane" => {
+            check_object_keys(
+                section,
+                &[
+                    "type",
+                    "material",
+                    "point",
+                    "normal",
+                    "horizontal",
+                    "vertical_x",
+                    "vertical_z",
+                ],
+            )?;
+            let mat = lookup_material(section, materials)?;
+            let given: Vec<&str> = ["point", "horizontal", "vertical_x", "vertical_z"]
+                .into_iter()
+                .filter(|key| section.get(key).is_some())
+                .collect();
+            let plane = match given.as_slice() {
+                ["point"] => Plane::new(section.vec3("point")?, section.vec3("normal")?, mat),
+                ["horizontal"] => Plane::horizontal(section.number("horizontal")?, mat),
+                ["vertical_x"] => Plane::vertical_x(section.number("vertical_x")?, mat),
+                ["vertical_z"] => Plane::vertical_z(section.number("vertical_z")?, mat),
+                _ => {
+                    return Err(error(
+                        section.line,
+                        None,
+                        "a plane needs exactly one of `point` (with `normal`), `horizontal`, `vertical_x` or `vertical_z`",
+                    ))
+                }
+            };
+            if given != ["point"] && section.get("normal").is_some() {
+                let field = section.require("normal")?;
+                return Err(error(
+                    field.line,
+                    Some("normal"),
+                    "only allowed together with `point`",
+                ));
+            }
+            Arc::new(plane)
+        }
+        "quad" => {
+            check_object_keys(section, &["type", "material", "corner", "u", "v"])?;
+            Arc::new(Quad::new(
+                section.vec3("corner")?,
+                section.vec3("u")?,
+                section.vec3("v")?,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "disk" => {
+            check_object_keys(section, &["type", "material", "center", "normal", "radius"])?;
+            Arc::new(Disk::new(
+                section.vec3("center")?,
+                section.direction("normal")?,
+                section.number("radius")?,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "annulus" => {
+            check_object_keys(
+                section,
+                &[
+                    "type",
+                    "material",
+                    "center",
+                    "normal",
+                    "inner_radius",
+                    "outer_radius",
+                ],
+            )?;
+            let inner_radius = section.number("inner_radius")?;
+            let outer_radius = section.number("outer_radius")?;
+            if inner_radius.abs() == outer_radius.abs() {
+                return Err(error(
+                    section.require("outer_radius")?.line,
+                    Some("outer_radius"),
+                    "must differ from inner_radius",
+                ));
+            }
+            Arc::new(Annulus::new(
+                section.vec3("center")?,
+                section.direction("normal")?,
+                inner_radius,
+                outer_radius,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "triangle" => {
+            check_object_keys(section, &["type", "material", "a", "b", "c"])?;
+            Arc::new(Triangle::new(
+                section.vec3("a")?,
+                section.vec3("b")?,
+                section.vec3("c")?,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "mesh" => {
+            check_object_keys(section, &["type", "material", "file"])?;
+            let field = section.require("file")?;
+            let file = section.string("file")?;
+            let mat = lookup_material(section, materials)?;
+
+            // Every object using the same file and material shares one mesh
+            let key = (base_dir.join(file), section.string("material")?.to_string());
+            if let Some(mesh) = meshes.get(&key) {
+                return Ok(mesh.clone());
+            }
+            let mesh = obj::load(&key.0, mat).map_err(|err| {
+                error(
+                    field.line,
+                    Some("file"),
+                    format!("cannot load `{}`: {}", file, err),
+                )
+            })?;
+            let mesh: Arc<dyn Hittable> = Arc::new(BvhNode::new(Arc::new(mesh).triangles()));
+            meshes.insert(key, mesh.clone());
+            mesh
+        }
+        "mandelbulb" => {
+            check_object_keys(
+                section,
+                &["type", "material", "power", "iterations", "bailout"],
+            )?;
+            let bulb = Mandelbulb::new(
+                section.number_or("power", 8.0)?,
+                section.integer_or("iterations", 10)?,
+                section.number_or("bailout", 2.0)?,
+            );
+            Arc::new(fractal::shape(bulb, lookup_material(section, materials)?))
+        }
+        "menger_sponge" => {
+            check_object_keys(section, &["type", "material", "iterations"])?;
+            let sponge = MengerSponge::new(section.integer_or("iterations", 4)?);
+            Arc::new(fractal::shape(sponge, lookup_material(section, materials)?))
+        }
+        "julia" => {
+            check_object_keys(section, &["type", "material", "c", "iterations", "bailout"])?;
+            let julia = QuaternionJulia::new(
+                section.quaternion("c")?,
+                section.integer_or("iterations", 12)?,
+                section.number_or("bailout", 4.0)?,
+            );
+            Arc::new(fractal::shape(julia, lookup_material(section, materials)?))
+        }
+        "union" | "intersection" | "difference" => {
+            check_object_keys(section, &["type", "a", "b"])?;
+            let op = match kind {
+                "union" => CsgOp::Union,
+                "intersection" => CsgOp::Intersection,
+                _ => CsgOp::Difference,
+            };
+            Arc::new(Csg::new(
+                op,
+                lookup_solid(section, "a", solids)?,
+                lookup_solid(section, "b", solids)?,
+            ))
+        }
+        "medium" => {
+            check_object_keys(section, &["type", "material", "boundary", "density"])?;
+            let density = section.number("density")?;
+            if density <= 0.0 {
+                return Err(error(
+                    section.require("density")?.line,
+                    Some("density"),
+                    "must be positive",
+                ));
+            }
+            Arc::new(ConstantMedium::new(
+                lookup_solid(section, "boundary", solids)?,
+                density,
+                lookup_material(section, materials)?,
+            ))
+        }
+        "volume" => {
+            check_object_keys(
+                section,
+                &[
+                    "type",
+                    "material",
+                    "file",
+                    "density",
+                    "emission",
+                    "temperature_scale",
+                ],
+            )?;
+            let field = section.require("file")?;
+            let file = section.string("file")?;
+            let grid = voxel::load(base_dir.join(file)).map_err(|err| {
+                error(
+                    field.line,
+                    Some("file"),
+                    format!("cannot load `{}`: {}", file, err),
+                )
+            })?;
+            let density = section.number_or("density", 1.0)?;
+            if density <= 0.0 {
+                return Err(error(
+                    section.require("density")?.line,
+                    Some("density"),
+                    "must be positive",
+                ));
+            }
+            let volume = GridVolume::new(
+                Arc::new(grid),
+                density,
+                lookup_material(section, materials)?,
+            )
+            .with_emission(section.vec3_or("emission", Vec3::new(1.0, 1.0, 1.0))?)
+            .with_temperature_scale(section.number_or("temperature_scale", 1.0)?);
+            Arc::new(volume)
+        }
+        _ => {
+            return Err(error(
+                section.require("type")?.line,
+                Some("type"),
+                format!("unknown object type `{}`", kind),
+            ))
+        }
+    };
+    Ok(object)
+}
+
+// Wrap an object in an instance if it has a transform
+fn place(object: Arc<dyn Hittable>, transform: Option<Mat4>) -> Arc<dyn Hittable> {
+    match transform {
+        Some(matrix) => Arc::new(Instance::new(object, matrix)),
+        None => object,
+    }
+}
+
+// Animate an object if it has keyframes
+fn animate(object: Arc<dyn Hittable>, motion: Option<Vec<Keyframe>>) -> Arc<dyn Hittable> {
+    match motion {
+        Some(keyframes) => Arc::new(AnimatedInstance::new(object, keyframes)),
+        None => object,
+    }
+}
+
+// `caps` of a cylinder or cone: "both" (the default), "base", "top" or "none"
+// `base` and `top`, which must be apart to give the shape an axis
+fn base_and_top(section: &Section) -> Result<(Point3, Point3), SceneError> {
+    let (base, top) = (section.vec3("base")?, section.vec3("top")?);
+    if (top - base).length_squared() == 0.0 {
+        return Err(error(
+            section.require("top")?.line,
+            Some("top"),
+            "must differ from `base`",
+        ));
+    }
+    Ok((base, top))
+}
+
+fn caps_or_both(section: &Section) -> Result<Caps, SceneError> {
+    let Some(field) = section.get("caps") else {
+        return Ok(Caps::BOTH);
+    };
+    match section.string("caps")? {
+        "both" => Ok(Caps::BOTH),
+        "base" => Ok(Caps {
+            base: true,
+            top: false,
+        }),
+        "top" => Ok(Caps {
+            base: false,
+            top: true,
+        }),
+        "none" => Ok(Caps::OPEN),
+        other => Err(error(
+            field.line,
+            Some("caps"),
+            format!(
+                "unknown caps `{}` (expected \"both\", \"base\", \"top\" or \"none\")",
+                other
+            ),
+        )),
+    }
+}
+
+const TRANSFORM_KEYS: &[&str] = &[
+    "scale",
+    "rotate",
+    "rotate_axis",
+    "rotate_angle",
+    "translate",
+];
+
+// Keyframed motion, applied after the transform
+const MOTION_KEYS: &[&str] = &[
+    "motion_times",
+    "motion_translate",
+    "motion_rotate",
+    "motion_scale",
+];
+
+// Like `check_keys`, also allowing the transform and motion fields every
+// object accepts
+fn check_object_keys(section: &Section, keys: &[&str]) -> Result<(), SceneError> {
+    let mut allowed = keys.to_vec();
+    allowed.extend_from_slice(TRANSFORM_KEYS);
+    allowed.extend_from_slice(MOTION_KEYS);
+    section.check_keys(&allowed)
+}
+
+// Transform of an object: scale, then rotate (about X, Y and Z in turn, then
+// about `rotate_axis`), then translate. `None` if no transform is given
+fn build_transform(section: &Section) -> Result<Option<Mat4>, SceneError> {
+    if TRANSFORM_KEYS.iter().all(|key| section.get(key).is_none()) {
+        return Ok(None);
+    }
+
+    let mut matrix = Mat4::identity();
+    if let Some(field) = section.get("scale") {
+        matrix = Mat4::scaling(to_scale(field)?) * matrix;
+    }
+    if section.get("rotate").is_some() {
+        let degrees = section.vec3("rotate")?;
+        for (axis, angle) in [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ]
+        .into_iter()
+        .zip([degrees.x(), degrees.y(), degrees.z()])
+        {
+            matrix = Mat4::rotation(axis, angle) * matrix;
+        }
+    }
+    if section.get("rotate_axis").is_some() || section.get("rotate_angle").is_some() {
+        let axis = section.vec3("rotate_axis")?;
+        if axis.near_zero() {
+            return Err(error(
+                section.require("rotate_axis")?.line,
+                Some("rotate_axis"),
+                "must not be zero",
+            ));
+        }
+        matrix = Mat4::rotation(axis, section.number("rotate_angle")?) * matrix;
+    }
+    if section.get("translate").is_some() {
+        matrix = Mat4::translation(section.vec3("translate")?) * matrix;
+    }
+    Ok(Some(matrix))
+}
+
+// A number, or an array of three numbers for a different factor per axis
+fn to_scale(field: &Field) -> Result<Vec3, SceneError> {
+    let factors = match field.value {
+        Value::Number(n) => Vec3::new(n, n, n),
+        _ => to_vec3(field).map_err(|_| {
+            error(
+                field.line,
+                Some(&field.key),
+                "expected a number or an array of three numbers",
+            )
+        })?,
+    };
+    if factors.x() * factors.y() * factors.z() == 0.0 {
+        return Err(error(field.line, Some(&field.key), "must not be zero"));
+    }
+    Ok(factors)
+}
+
+// Keyframes of an object's motion: at each of `motion_times` the object is
+// scaled by `motion_scale`, rotated by `motion_rotate` (about X, Y and Z in
+// turn) and translated by `motion_translate`, any of which may be left out.
+// `None` if the object does not move
+fn build_motion(section: &Section) -> Result<Option<Vec<Keyframe>>, SceneError> {
+    if MOTION_KEYS.iter().all(|key| section.get(key).is_none()) {
+        return Ok(None);
+    }
+
+    let times_field = section.require("motion_times")?;
+    let times = motion_values(section, "motion_times", 0, to_number)?;
+    if times.is_empty() {
+        return Err(error(
+            times_field.line,
+            Some("motion_times"),
+            "expected at least one time",
+        ));
+    }
+    let values = |key, read: fn(&Field) -> Result<Vec3, SceneError>, default| {
+        if section.get(key).is_none() {
+            return Ok(vec![default; times.len()]);
+        }
+        motion_values(section, key, times.len(), read)
+    };
+    let translations = values("motion_translate", to_vec3, Vec3::new(0.0, 0.0, 0.0))?;
+    let rotations = values("motion_rotate", to_vec3, Vec3::new(0.0, 0.0, 0.0))?;
+    let scales = values("motion_scale", to_scale, Vec3::new(1.0, 1.0, 1.0))?;
+
+    Ok(Some(
+        (0..times.len())
+            .map(|i| {
+                Keyframe::new(
+                    times[i],
+                    translations[i],
+                    Quaternion::euler(rotations[i]),
+                    scales[i],
+                )
+            })
+            .collect(),
+    ))
+}
+
+// Each item of the array `key`, read by `read`; there must be `count` of
+// them unless `count` is zero
+fn motion_values<T>(
+    section: &Section,
+    key: &str,
+    count: usize,
+    read: fn(&Field) -> Result<T, SceneError>,
+) -> Result<Vec<T>, SceneError> {
+    let field = section.require(key)?;
+    let Value::Array(items) = &field.value else {
+        return Err(type_error(field, "an array", &field.value));
+    };
+    if count > 0 && items.len() != count {
+        return Err(error(
+            field.line,
+            Some(key),
+            format!("expected one entry for each of the {} motion times", count),
+        ));
+    }
+    items
+        .iter()
+        .map(|item| {
+            read(&Field {
+                key: field.key.clone(),
+                value: item.clone(),
+                line: field.line,
+            })
+        })
+        .collect()
+}
+
+/// The scene rendered when no scene file is given
+pub const DEFAULT_SCENE: &str = include_str!("../scenes/default.toml");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Line and field of the error from parsing `text`
+    fn parse_error(text: &str) -> (usize, Option<String>) {
+        match parse(text) {
+            Err(SceneError::Parse { line, field, .. }) => (line, field),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("scene parsed"),
+        }
+    }
+
+    #[test]
+    fn errors_report_line_and_field() {
+        let text = "\
+[render]
+image_width = 100
+samples_per_pixel = 4
+
+[camera]
+lookfrom = [0.0, 0.0, 5.0]
+lookat = [0.0, 0.0, 0.0]
+vfov = 40.0
+
+[materials.red]
+type = \"lambertian\"
+albedo = [0.8, 0.1, 0.1]
+
+[[objects]]
+type = \"sphere\"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = \"red\"
+";
+        parse(text).unwrap();
+
+        // Each case replaces some text and gives the line and field of the error
+        let cases = [
+            ("[render]", "seed = 1\n[render]", 1, Some("seed")),
+            (
+                "image_width = 100",
+                "image_width = 100\nimage_width = 1",
+                3,
+                Some("image_width"),
+            ),
+            (
+                "samples_per_pixel = 4",
+                "samples_per_pixel = 2.5",
+                3,
+                Some("samples_per_pixel"),
+            ),
+            ("vfov = 40.0", "vfov 40.0", 8, None),
+            ("[materials.red]", "[material.red]", 10, None),
+            ("[[objects]]", "[objects]", 14, None),
+            (
+                "center = [0.0, 0.0, 0.0]",
+                "center = [0.0, 0.0]",
+                16,
+                Some("center"),
+            ),
+            ("radius = 1.0", "radius = \"big\"", 17, Some("radius")),
+            ("radius = 1.0", "radius = 1.0 2.0", 17, Some("radius")),
+            ("radius = 1.0", "radus = 1.0", 17, Some("radus")),
+            ("radius = 1.0\n", "", 14, Some("radius")),
+            (
+                "material = \"red\"",
+                "material = \"blue\"",
+                18,
+                Some("material"),
+            ),
+        ];
+        for (from, to, line, field) in cases {
+            let broken = text.replacen(from, to, 1);
+            assert_eq!(
+                parse_error(&broken),
+                (line, field.map(str::to_string)),
+                "replacing `{}` with `{}`",
+                from,
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn cylinders_and_cones_need_length() {
+        let text = "\
+[camera]
+lookfrom = [0.0, 0.0, 5.0]
+lookat = [0.0, 0.0, 0.0]
+vfov = 40.0
+
+[materials.red]
+type = \"lambertian\"
+albedo = [0.8, 0.1, 0.1]
+
+[[objects]]
+type = \"cylinder\"
+base = [0.0, 0.0, 0.0]
+top = [0.0, 1.0, 0.0]
+radius = 1.0
+material = \"red\"
+";
+        parse(text).unwrap();
+        let cases = [
+            ("top = [0.0, 1.0, 0.0]", "top = [0.0, 0.0, 0.0]", 13, "top"),
+            (
+                "base = [0.0, 0.0, 0.0]\ntop = [0.0, 1.0, 0.0]",
+                "center = [0.0, 0.0, 0.0]\nheight = 0.0",
+                13,
+                "height",
+            ),
+            (
+                "cylinder\"\nbase = [0.0, 0.0, 0.0]\ntop = [0.0, 1.0, 0.0]\nradius",
+                "cone\"\nbase = [0.0, 2.0, 0.0]\ntop = [0.0, 2.0, 0.0]\nbase_radius",
+                13,
+                "top",
+            ),
+        ];
+        for (from, to, line, field) in cases {
+            let broken = text.replacen(from, to, 1);
+            assert_eq!(
+                parse_error(&broken),
+                (line, Some(field.to_string())),
+                "{}",
+                to
+            );
+        }
+
+        // A capsule without length is a sphere
+        let ball = text
+            .replace("cylinder", "capsule")
+            .replace("top = [0.0, 1.0, 0.0]", "top = [0.0, 0.0, 0.0]");
+        parse(&ball).unwrap();
+    }
+
+    #[test]
+    fn torus_axis_must_not_be_zero() {
+        let text = "\
+[camera]
+lookfrom = [0.0, 0.0, 5.0]
+lookat = [0.0, 0.0, 0.0]
+vfov = 40.0
+
+[materials.red]
+type = \"lambertian\"
+albedo = [0.8, 0.1, 0.1]
+
+[[objects]]
+type = \"torus\"
+center = [0.0, 0.0, 0.0]
+axis = [0.0, 0.0, 0.0]
+major_radius = 1.0
+minor_radius = 0.25
+material = \"red\"
+";
+        assert_eq!(parse_error(text), (13, Some("axis".to_string())));
+        parse(&text.replace("axis = [0.0, 0.0, 0.0]", "axis = [0.0, 0.0, 2.0]")).unwrap();
+    }
+
+    #[test]
+    fn disk_and_annulus_normals_must_not_be_zero() {
+        let text = "\
+[camera]
+lookfrom = [0.0, 0.0, 5.0]
+lookat = [0.0, 0.0, 0.0]
+vfov = 40.0
+
+[materials.red]
+type = \"lambertian\"
+albedo = [0.8, 0.1, 0.1]
+
+[[objects]]
+type = \"disk\"
+center = [0.0, 0.0, 0.0]
+normal = [0.0, 0.0, 0.0]
+radius = 1.0
+material = \"red\"
+
+[[objects]]
+type = \"annulus\"
+center = [0.0, 0.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+inner_radius = 0.5
+outer_radius = 1.0
+material = \"red\"
+";
+        assert_eq!(parse_error(text), (13, Some("normal".to_string())));
+        let text = text.replacen("normal = [0.0, 0.0, 0.0]", "normal = [0.0, 0.0, 1.0]", 1);
+        parse(&text).unwrap();
+        let text = text.replace("normal = [0.0, 1.0, 0.0]", "normal = [0.0, 0.0, 0.0]");
+        assert_eq!(parse_error(&text), (20, Some("normal".to_string())));
+    }
+
+    #[test]
+    fn medium_density_must_be_positive() {
+        let text = "\
+[camera]
+lookfrom = [0.0, 0.0, 5.0]
+lookat = [0.0, 0.0, 0.0]
+vfov = 40.0
+
+[materials.fog]
+type = \"isotropic\"
+albedo = [0.9, 0.9, 0.9]
+
+[solids.ball]
+type = \"sphere\"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = \"fog\"
+
+[[objects]]
+type = \"medium\"
+boundary = \"ball\"
+density = 0.0
+material = \"fog\"
+";
+        assert_eq!(parse_error(text), (19, Some("density".to_string())));
+        parse(&text.replace("density = 0.0", "density = 0.5")).unwrap();
+    }
+
+    #[test]
+    fn annulus_needs_width() {
+        let text = "\
+[camera]
+lookfrom = [0.0, 0.0, 5.0]
+lookat = [0.0, 0.0, 0.0]
+vfov = 40.0
+
+[materials.metal]
+type = \"metal\"
+albedo = [0.8, 0.8, 0.8]
+fuzz = 0.0
+
+[[objects]]
+type = \"annulus\"
+center = [0.0, 0.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+inner_radius = 1.0
+outer_radius = 1.0
+material = \"metal\"
+";
+        assert_eq!(parse_error(text), (16, Some("outer_radius".to_string())));
+        parse(&text.replace("outer_radius = 1.0", "outer_radius = 2.0")).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::common;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+/// A signed distance function: the distance from a point to the surface of a
+/// shape, negative inside it
+///
+/// The value may underestimate the true distance but must never exceed it, or
+/// sphere tracing can step through the surface. Any `Fn(Point3) -> f64`
+/// closure is an `Sdf`.
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: Point3) -> f64;
+
+    /// Value from 0 to 1 for coloring the surface at `p`, such as the orbit
+    /// trap of a fractal; zero unless overridden
+    fn trap(&self, _p: Point3) -> f64 {
+        0.0
+    }
+}
+
+impl<F: Fn(Point3) -> f64 + Send + Sync> Sdf for F {
+    fn distance(&self, p: Point3) -> f64 {
+        self(p)
+    }
+}
+
+/// A shape given by a signed distance function, found by sphere tracing
+///
+/// Rays step along by the distance to the surface, which can never overshoot
+/// it, until they come within `epsilon` of the surface. Rays that need more
+/// than `max_steps` steps, such as ones grazing the surface, miss.
+pub struct SdfShape {
+    sdf: Arc<dyn Sdf>,
+    bounds: Aabb,
+    mat: Arc<dyn Material>,
+    epsilon: f64,
+    max_steps: usize,
+}
+
+impl SdfShape {
+    /// Create a shape from a distance function
+    ///
+    /// # Arguments
+    /// * `sdf` - The distance function
+    /// * `bounds` - A box enclosing the whole surface; rays are only marched
+    ///   inside it
+    /// * `mat` - The material of the shape
+    pub fn new(sdf: Arc<dyn Sdf>, bounds: Aabb, mat: Arc<dyn Material>) -> SdfShape {
+        SdfShape {
+            sdf,
+            bounds,
+            mat,
+            epsilon: 1e-4,
+            max_steps: 256,
+        }
+    }
+
+    /// Change how close counts as a hit (1e-4 by default) and how many steps
+    /// a ray may take (256 by default)
+    pub fn with_limits(mut self, epsilon: f64, max_steps: usize) -> SdfShape {
+        self.epsilon = epsilon;
+        self.max_steps = max_steps;
+        self
+    }
+
+    // Outward normal: the gradient of the distance, by central differences
+    fn normal(&self, p: Point3) -> Vec3 {
+        let h = self.epsilon;
+        let d = |offset: Vec3| self.sdf.distance(p + offset) - self.sdf.distance(p - offset);
+        vec3::unit_vector(Vec3::new(
+            d(Vec3::new(h, 0.0, 0.0)),
+            d(Vec3::new(0.0, h, 0.0)),
+            d(Vec3::new(0.0, 0.0, h)),
+        ))
+    }
+}
+
+impl Hittable for SdfShape {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let Some((t_start, t_end)) = self.bounds.clip(r, t_min, t_max) else {
+            return false;
+        };
+        // Distances are measured in space, `t` in multiples of the direction
+        let len = r.direction().length();
+
+        // March towards the surface from whichever side the ray starts on. A
+        // ray coming from outside the bounds is outside the shape. One
+        // starting on the surface, as scattered rays do, is inside if it
+        // heads into the shape, and does not count as a hit until it has
+        // moved away from the surface
+        let mut t = t_start;
+        let start = self.sdf.distance(r.at(t));
+        let from_outside = t_start > t_min;
+        let side = if from_outside {
+            1.0
+        } else if start.abs() >= self.epsilon {
+            start.signum()
+        } else if vec3::dot(self.normal(r.at(t)), r.direction()) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let mut left_surface = from_outside || start.abs() >= self.epsilon;
+
+        for _ in 0..self.max_steps {
+            let distance = side * self.sdf.distance(r.at(t));
+            if distance < self.epsilon {
+                if left_surface {
+                    rec.t = t;
+                    rec.p = r.at(t);
+                    rec.set_face_normal(r, self.normal(rec.p));
+                    rec.trap = self.sdf.trap(rec.p);
+                    rec.mat = Some(self.mat.clone());
+                    return true;
+                }
+            } else {
+                left_surface = true;
+            }
+
+            t += f64::max(distance, self.epsilon) / len;
+            if t > t_end {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+/// Sphere with the given center and radius
+pub fn sphere(center: Point3, radius: f64) -> Arc<dyn Sdf> {
+    Arc::new(move |p: Point3| (p - center).length() - radius)
+}
+
+/// Axis-aligned box with the given center and half the length of each side
+pub fn cuboid(center: Point3, half_size: Vec3) -> Arc<dyn Sdf> {
+    Arc::new(move |p: Point3| {
+        let q = p - center;
+        let q = Vec3::new(
+            q.x().abs() - half_size.x(),
+            q.y().abs() - half_size.y(),
+            q.z().abs() - half_size.z(),
+        );
+        let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0));
+        outside.length() + q.x().max(q.y()).max(q.z()).min(0.0)
+    })
+}
+
+/// Torus around the Y axis, with the given distance from the center to the
+/// middle of the tube and tube radius
+pub fn torus(center: Point3, major_radius: f64, minor_radius: f64) -> Arc<dyn Sdf> {
+    Arc::new(move |p: Point3| {
+        let q = p - center;
+        let ring = f64::sqrt(q.x() * q.x() + q.z() * q.z()) - major_radius;
+        f64::sqrt(ring * ring + q.y() * q.y()) - minor_radius
+    })
+}
+
+/// Union of two shapes, blended over a distance of about `k` where they meet
+pub fn smooth_union(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>, k: f64) -> Arc<dyn Sdf> {
+    Arc::new(move |p: Point3| {
+        let (da, db) = (a.distance(p), b.distance(p));
+        if k <= 0.0 {
+            return da.min(db);
+        }
+        let h = common::clamp(0.5 + 0.5 * (db - da) / k, 0.0, 1.0);
+        db + (da - db) * h - k * h * (1.0 - h)
+    })
+}
+
+/// Shape `a` with shape `b` cut away
+pub fn subtraction(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>) -> Arc<dyn Sdf> {
+    Arc::new(move |p: Point3| a.distance(p).max(-b.distance(p)))
+}
+
+/// Copies of a shape repeated forever, `period` apart along each axis
+///
+/// An axis with a period of zero is not repeated. The shape should fit in the
+/// cell around the origin, half a period each way.
+pub fn repeat(sdf: Arc<dyn Sdf>, period: Vec3) -> Arc<dyn Sdf> {
+    Arc::new(move |p: Point3| {
+        let mut q = p;
+        for axis in 0..3 {
+            if period[axis] > 0.0 {
+                q[axis] -= period[axis] * (p[axis] / period[axis]).round();
+            }
+        }
+        sdf.distance(q)
+    })
+}
+
+/// A shape grown by `radius` in every direction, which rounds its edges
+pub fn round(sdf: Arc<dyn Sdf>, radius: f64) -> Arc<dyn Sdf> {
+    Arc::new(move |p: Point3| sdf.distance(p) - radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let ball = sphere(Point3::new(1.0, 0.0, 0.0), 2.0);
+        assert_near(ball.distance(Point3::new(1.0, 0.0, 0.0)), -2.0);
+        assert_near(ball.distance(Point3::new(1.0, 5.0, 0.0)), 3.0);
+
+        let cube = cuboid(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 3.0));
+        assert_near(cube.distance(Point3::new(0.0, 0.0, 0.0)), -1.0);
+        assert_near(cube.distance(Point3::new(3.0, 0.0, 0.0)), 2.0);
+        // Nearest the corner
+        assert_near(cube.distance(Point3::new(4.0, 6.0, 3.0)), 5.0);
+
+        let ring = torus(Point3::new(0.0, 1.0, 0.0), 2.0, 0.5);
+        assert_near(ring.distance(Point3::new(2.0, 1.0, 0.0)), -0.5);
+        assert_near(ring.distance(Point3::new(0.0, 1.0, 0.0)), 1.5);
+        assert_near(ring.distance(Point3::new(0.0, 1.0, -5.0)), 2.5);
+    }
+
+    #[test]
+    fn smooth_union_approaches_union() {
+        let a = sphere(Point3::new(-1.0, 0.0, 0.0), 1.0);
+        let b = sphere(Point3::new(1.5, 0.0, 0.0), 1.0);
+        let points = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.25, 0.3, 0.0),
+            Point3::new(-1.0, 2.0, 1.0),
+        ];
+        for p in points {
+            let union = a.distance(p).min(b.distance(p));
+            assert_near(smooth_union(a.clone(), b.clone(), 0.0).distance(p), union);
+            let nearly = smooth_union(a.clone(), b.clone(), 1e-9).distance(p);
+            assert!((nearly - union).abs() < 1e-8);
+            // Blending only ever adds material
+            assert!(smooth_union(a.clone(), b.clone(), 0.5).distance(p) <= union);
+        }
+    }
+
+    #[test]
+    fn subtraction_cuts_away() {
+        let cube = cuboid(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+        let hole = sphere(Point3::new(0.0, 0.0, 0.0), 0.5);
+        let cut = subtraction(cube, hole);
+        assert_near(cut.distance(Point3::new(0.0, 0.0, 0.0)), 0.5);
+        assert_near(cut.distance(Point3::new(0.75, 0.0, 0.0)), -0.25);
+        assert_near(cut.distance(Point3::new(2.0, 0.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn repeat_is_periodic() {
+        let ball = sphere(Point3::new(0.0, 0.0, 0.0), 0.5);
+        let grid = repeat(ball.clone(), Vec3::new(3.0, 0.0, 2.0));
+        let p = Point3::new(0.4, 0.7, -0.3);
+        let d = grid.distance(p);
+        assert_near(d, ball.distance(p));
+        assert_near(grid.distance(p + Vec3::new(3.0, 0.0, 0.0)), d);
+        assert_near(grid.distance(p + Vec3::new(-6.0, 0.0, 4.0)), d);
+        // The Y axis has a period of zero, so is not repeated
+        let above = p + Vec3::new(0.0, 3.0, 0.0);
+        assert_near(grid.distance(above), ball.distance(above));
+        assert!(grid.distance(above) > 3.0);
+    }
+
+    #[test]
+    fn round_grows_shape() {
+        let cube = cuboid(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+        let rounded = round(cube.clone(), 0.25);
+        for p in [Point3::new(2.0, 0.0, 0.0), Point3::new(2.0, 2.0, 2.0)] {
+            assert_near(rounded.distance(p), cube.distance(p) - 0.25);
+        }
+        assert_near(rounded.distance(Point3::new(1.25, 0.0, 0.0)), 0.0);
+    }
+}
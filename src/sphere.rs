@@ -1,18 +1,21 @@
-use std::rc::Rc;
- 
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::common::{self, Rng};
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::onb::Onb;
 use crate::ray::Ray;
-use crate::vec3::{self, Point3};
- 
+use crate::vec3::{self, Point3, Vec3};
+
 pub struct Sphere {
     center: Point3,
     radius: f64,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
 }
- 
+
 impl Sphere {
-    pub fn new(cen: Point3, r: f64, m: Rc<dyn Material>) -> Sphere {
+    pub fn new(cen: Point3, r: f64, m: Arc<dyn Material>) -> Sphere {
         Sphere {
             center: cen,
             radius: r,
@@ -20,34 +23,220 @@ impl Sphere {
         }
     }
 }
- 
+
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let oc = r.origin() - self.center;
-        let a = r.direction().length_squared();
-        let half_b = vec3::dot(oc, r.direction());
-        let c = oc.length_squared() - self.radius * self.radius;
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return false;
+        hit_sphere(self.center, self.radius, &self.mat, r, t_min, t_max, rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        // Directions are sampled from the cone the sphere subtends, which
+        // does not exist from inside the sphere
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 0.0;
+        }
+
+        let mut rec = HitRecord::new();
+        if !self.hit(
+            &Ray::new(origin, direction, 0.0),
+            0.001,
+            common::INFINITY,
+            &mut rec,
+        ) {
+            return 0.0;
         }
- 
-        let sqrt_d = f64::sqrt(discriminant);
- 
-        // Find the nearest root that lies in the acceptable range
-        let mut root = (-half_b - sqrt_d) / a;
+
+        let cos_theta_max = f64::sqrt(1.0 - radius_squared / distance_squared);
+        let solid_angle = 2.0 * common::PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return vec3::random_unit_vector(rng);
+        }
+
+        // Uniform direction within the cone around `direction`
+        let r1 = common::random_double(rng);
+        let r2 = common::random_double(rng);
+        let cos_theta_max = f64::sqrt(1.0 - radius_squared / distance_squared);
+        let z = 1.0 + r2 * (cos_theta_max - 1.0);
+        let phi = 2.0 * common::PI * r1;
+        let sin_theta = f64::sqrt(1.0 - z * z);
+        let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z);
+        Onb::new(direction).local(local)
+    }
+}
+
+/// A sphere whose center moves in a straight line, for motion blur
+///
+/// The sphere holds still at its first position before `time0` and at its
+/// last after `time1`.
+pub struct MovingSphere {
+    center0: Point3,
+    center1: Point3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    /// Create a moving sphere
+    ///
+    /// # Arguments
+    /// * `center0` - The center at `time0`
+    /// * `center1` - The center at `time1`
+    /// * `time0` - The time the sphere starts moving
+    /// * `time1` - The time the sphere stops moving
+    /// * `radius` - The radius of the sphere
+    /// * `mat` - The material of the sphere
+    pub fn new(
+        center0: Point3,
+        center1: Point3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            mat,
+        }
+    }
+
+    /// Center of the sphere at the given time
+    pub fn center(&self, time: f64) -> Point3 {
+        let f = if self.time1 > self.time0 {
+            common::clamp((time - self.time0) / (self.time1 - self.time0), 0.0, 1.0)
+        } else if time < self.time0 {
+            0.0
+        } else {
+            1.0
+        };
+        self.center0 + f * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let center = self.center(r.time());
+        hit_sphere(center, self.radius, &self.mat, r, t_min, t_max, rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // The sphere only ever lies along the segment between its two centers
+        let r = Vec3::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+        let box0 = Aabb::new(self.center0 - r, self.center0 + r);
+        let box1 = Aabb::new(self.center1 - r, self.center1 + r);
+        Some(aabb::surrounding_box(box0, box1))
+    }
+}
+
+fn hit_sphere(
+    center: Point3,
+    radius: f64,
+    mat: &Arc<dyn Material>,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+    rec: &mut HitRecord,
+) -> bool {
+    let oc = r.origin() - center;
+    let a = r.direction().length_squared();
+    let half_b = vec3::dot(oc, r.direction());
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return false;
+    }
+
+    let sqrt_d = f64::sqrt(discriminant);
+
+    // Find the nearest root that lies in the acceptable range
+    let mut root = (-half_b - sqrt_d) / a;
+    if root <= t_min || t_max <= root {
+        root = (-half_b + sqrt_d) / a;
         if root <= t_min || t_max <= root {
-            root = (-half_b + sqrt_d) / a;
-            if root <= t_min || t_max <= root {
-                return false;
+            return false;
+        }
+    }
+
+    rec.t = root;
+    rec.p = r.at(rec.t);
+    let outward_normal = (rec.p - center) / radius;
+    rec.set_face_normal(r, outward_normal);
+    rec.mat = Some(mat.clone());
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn moving_sphere() -> MovingSphere {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        MovingSphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(4.0, 2.0, 0.0),
+            1.0,
+            3.0,
+            0.5,
+            mat,
+        )
+    }
+
+    #[test]
+    fn moving_sphere_center_clamps_to_its_ends() {
+        let sphere = moving_sphere();
+        assert!((sphere.center(1.0) - Point3::new(0.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((sphere.center(2.0) - Point3::new(2.0, 1.0, 0.0)).length() < 1e-12);
+        assert!((sphere.center(3.0) - Point3::new(4.0, 2.0, 0.0)).length() < 1e-12);
+        assert!((sphere.center(-4.0) - Point3::new(0.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((sphere.center(9.0) - Point3::new(4.0, 2.0, 0.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn moving_sphere_hit_follows_time() {
+        let sphere = moving_sphere();
+        let mut rec = HitRecord::new();
+        for (time, x) in [(0.0, 0.0), (2.0, 2.0), (5.0, 4.0)] {
+            let y = x / 2.0;
+            let r = Ray::new(Point3::new(x, y, 5.0), Vec3::new(0.0, 0.0, -1.0), time);
+            assert!(sphere.hit(&r, 0.001, common::INFINITY, &mut rec));
+            assert!((rec.t - 4.5).abs() < 1e-9);
+            assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+        }
+        let r = Ray::new(Point3::new(4.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 1.0);
+        assert!(!sphere.hit(&r, 0.001, common::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn moving_sphere_box_covers_its_path() {
+        let sphere = moving_sphere();
+        let bbox = sphere.bounding_box().unwrap();
+        for i in 0..=40 {
+            let c = sphere.center(i as f64 / 10.0);
+            for axis in 0..3 {
+                assert!(bbox.min()[axis] <= c[axis] - 0.5 + 1e-9);
+                assert!(c[axis] + 0.5 <= bbox.max()[axis] + 1e-9);
             }
         }
- 
-        rec.t = root;
-        rec.p = r.at(rec.t);
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(r, outward_normal);
-        rec.mat = Some(self.mat.clone());
-        true
     }
-}
\ No newline at end of file
+}
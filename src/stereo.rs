@@ -0,0 +1,107 @@
+use crate::camera::Camera;
+use crate::common::Rng;
+use crate::ray::Ray;
+
+/// How the two views of a stereo pair share one image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// Left eye in the left half, right eye in the right half
+    SideBySide,
+    /// Left eye in the top half, right eye in the bottom half
+    TopBottom,
+}
+
+impl StereoLayout {
+    /// Aspect ratio of each eye's view in an image of the given aspect ratio
+    pub fn eye_aspect_ratio(self, aspect_ratio: f64) -> f64 {
+        match self {
+            StereoLayout::SideBySide => aspect_ratio / 2.0,
+            StereoLayout::TopBottom => aspect_ratio * 2.0,
+        }
+    }
+}
+
+/// A pair of cameras, one for each eye, rendered into two halves of an image
+///
+/// Give each eye its own position with the cameras' `with_eye` methods. For
+/// 360 degree video, pair two `EquirectangularCamera`s top and bottom, in an
+/// image as wide as it is tall.
+pub struct StereoCamera {
+    left: Box<dyn Camera>,
+    right: Box<dyn Camera>,
+    layout: StereoLayout,
+}
+
+impl StereoCamera {
+    /// Create a stereo pair
+    ///
+    /// # Arguments
+    /// * `left` - The camera for the left eye
+    /// * `right` - The camera for the right eye
+    /// * `layout` - Where each eye's view goes in the image
+    pub fn new(
+        left: Box<dyn Camera>,
+        right: Box<dyn Camera>,
+        layout: StereoLayout,
+    ) -> StereoCamera {
+        StereoCamera {
+            left,
+            right,
+            layout,
+        }
+    }
+}
+
+impl Camera for StereoCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut Rng) -> Option<Ray> {
+        match self.layout {
+            StereoLayout::SideBySide if s < 0.5 => self.left.get_ray(2.0 * s, t, rng),
+            StereoLayout::SideBySide => self.right.get_ray(2.0 * s - 1.0, t, rng),
+            // `t` runs up the image
+            StereoLayout::TopBottom if t >= 0.5 => self.left.get_ray(s, 2.0 * t - 1.0, rng),
+            StereoLayout::TopBottom => self.right.get_ray(s, 2.0 * t, rng),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::PerspectiveCamera;
+    use crate::common;
+    use crate::vec3::{self, Point3, Vec3};
+
+    #[test]
+    fn layouts_split_the_image_between_eyes() {
+        assert_eq!(StereoLayout::SideBySide.eye_aspect_ratio(2.0), 1.0);
+        assert_eq!(StereoLayout::TopBottom.eye_aspect_ratio(1.0), 2.0);
+
+        let eye = |offset| -> Box<dyn Camera> {
+            Box::new(
+                PerspectiveCamera::new(
+                    Point3::new(0.0, 0.0, 0.0),
+                    Point3::new(0.0, 0.0, -1.0),
+                    Vec3::new(0.0, 1.0, 0.0),
+                    90.0,
+                    1.0,
+                )
+                .with_eye(offset, common::INFINITY),
+            )
+        };
+        let mut rng = common::pixel_rng(1, 0);
+        let side_by_side = StereoCamera::new(eye(-1.0), eye(1.0), StereoLayout::SideBySide);
+        let top_bottom = StereoCamera::new(eye(-1.0), eye(1.0), StereoLayout::TopBottom);
+        // Which eye sees each point, and where in its own view
+        for (cam, s, t, x, direction) in [
+            (&side_by_side, 0.25, 0.5, -1.0, Vec3::new(0.0, 0.0, -1.0)),
+            (&side_by_side, 1.0, 1.0, 1.0, Vec3::new(1.0, 1.0, -1.0)),
+            (&top_bottom, 0.5, 0.75, -1.0, Vec3::new(0.0, 0.0, -1.0)),
+            (&top_bottom, 0.0, 0.0, 1.0, Vec3::new(-1.0, -1.0, -1.0)),
+        ] {
+            let r = cam.get_ray(s, t, &mut rng).unwrap();
+            assert_eq!(r.origin().x(), x);
+            let d = vec3::unit_vector(r.direction()) - vec3::unit_vector(direction);
+            assert!(d.length() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::common;
+use crate::disk;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+/// A ring-shaped surface: a circle of radius `minor_radius` swept around a
+/// circle of radius `major_radius`
+pub struct Torus {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+    mat: Arc<dyn Material>,
+    // Basis whose `w` axis is the axis of symmetry
+    uvw: Onb,
+}
+
+impl Torus {
+    /// Create a torus
+    ///
+    /// # Arguments
+    /// * `center` - The center of the ring
+    /// * `axis` - The axis the ring goes around
+    /// * `major_radius` - The distance from the center to the middle of the tube
+    /// * `minor_radius` - The radius of the tube
+    /// * `mat` - The material of the torus
+    ///
+    /// # Panics
+    /// If `axis` is the zero vector.
+    pub fn new(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> Torus {
+        assert!(axis.length_squared() > 0.0, "a torus needs an axis");
+        Torus {
+            center,
+            major_radius: major_radius.abs(),
+            minor_radius: minor_radius.abs(),
+            mat,
+            uvw: Onb::new(axis),
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // Work with a unit direction in the torus's own frame, where the axis
+        // is z; `len` converts distances back to the ray's parameter
+        let len = r.direction().length();
+        let oc = r.origin() - self.center;
+        let dir = r.direction() / len;
+        let to_local = |a: Vec3| {
+            Vec3::new(
+                vec3::dot(a, self.uvw.u()),
+                vec3::dot(a, self.uvw.v()),
+                vec3::dot(a, self.uvw.w()),
+            )
+        };
+        let d = to_local(dir);
+
+        // Restart the ray at its point closest to the center, so that the
+        // coefficients stay small however far away the origin is
+        let t0 = -vec3::dot(oc, dir);
+        let o = to_local(oc) + t0 * d;
+
+        // Every point of the torus lies within its bounding sphere
+        let extent = self.major_radius + self.minor_radius;
+        if o.length_squared() > extent * extent {
+            return false;
+        }
+        let lo = f64::max(-extent, t_min * len - t0);
+        let hi = f64::min(extent, t_max * len - t0);
+        if lo > hi {
+            return false;
+        }
+
+        // (|p|² + R² - r²)² = 4R²(px² + py²) along p = o + s d
+        let big_r2 = self.major_radius * self.major_radius;
+        let n = vec3::dot(o, d);
+        let k = o.length_squared() + big_r2 - self.minor_radius * self.minor_radius;
+        let coeffs = [
+            1.0,
+            4.0 * n,
+            4.0 * n * n + 2.0 * k - 4.0 * big_r2 * (d.x() * d.x() + d.y() * d.y()),
+            4.0 * n * k - 8.0 * big_r2 * (o.x() * d.x() + o.y() * d.y()),
+            k * k - 4.0 * big_r2 * (o.x() * o.x() + o.y() * o.y()),
+        ];
+        let mut roots = [0.0; 4];
+        if real_roots(&coeffs, lo, hi, &mut roots) == 0 {
+            return false;
+        }
+        let s = roots[0];
+
+        let p = o + s * d;
+        let ring = f64::sqrt(p.x() * p.x() + p.y() * p.y());
+        // Nearest point on the circle running through the middle of the tube
+        let core = if ring > 0.0 {
+            self.major_radius / ring * Vec3::new(p.x(), p.y(), 0.0)
+        } else {
+            Vec3::new(self.major_radius, 0.0, 0.0)
+        };
+        let outward_normal = self.uvw.local(vec3::unit_vector(p - core));
+
+        rec.t = (t0 + s) / len;
+        rec.p = r.at(rec.t);
+        // Angle around the axis, and angle around the tube starting outside
+        let turn = 2.0 * common::PI;
+        rec.u = f64::atan2(p.y(), p.x()).rem_euclid(turn) / turn;
+        rec.v = f64::atan2(p.z(), ring - self.major_radius).rem_euclid(turn) / turn;
+        rec.set_face_normal(r, outward_normal);
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let ring = disk::bounding_box(self.center, self.uvw.w(), self.major_radius);
+        let tube = Vec3::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        Some(Aabb::new(ring.min() - tube, ring.max() + tube))
+    }
+}
+
+// Value of the polynomial with the given coefficients, highest power first
+fn evaluate(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().fold(0.0, |acc, &c| acc * x + c)
+}
+
+// Real roots of a polynomial of degree 1 to 4 that lie within [lo, hi], in
+// ascending order; returns how many were written to `roots`
+//
+// Between two neighbouring roots of its derivative a polynomial is monotonic,
+// so each of those intervals holds at most one root, which can be bracketed
+// and polished without the cancellation closed-form quartic formulas suffer
+// near double roots (rays grazing the surface).
+fn real_roots(coeffs: &[f64], lo: f64, hi: f64, roots: &mut [f64; 4]) -> usize {
+    let degree = coeffs.len() - 1;
+    if degree == 1 {
+        let x = -coeffs[1] / coeffs[0];
+        if (lo..=hi).contains(&x) {
+            roots[0] = x;
+            return 1;
+        }
+        return 0;
+    }
+
+    let mut derivative = [0.0; 4];
+    for (i, c) in derivative[..degree].iter_mut().enumerate() {
+        *c = coeffs[i] * (degree - i) as f64;
+    }
+    let derivative = &derivative[..degree];
+    let mut critical = [0.0; 4];
+    let critical_count = real_roots(derivative, lo, hi, &mut critical);
+
+    let mut count = 0;
+    let mut a = lo;
+    let mut fa = evaluate(coeffs, a);
+    for &b in critical[..critical_count].iter().chain([hi].iter()) {
+        let fb = evaluate(coeffs, b);
+        if (fa < 0.0) != (fb < 0.0) {
+            roots[count] = bracketed_root(coeffs, derivative, a, b, fa < 0.0);
+            count += 1;
+        }
+        a = b;
+        fa = fb;
+    }
+    count
+}
+
+// Root of a polynomial that changes sign between `a` and `b`, by Newton's
+// method falling back to bisection whenever a step leaves the bracket
+fn bracketed_root(
+    coeffs: &[f64],
+    derivative: &[f64],
+    mut a: f64,
+    mut b: f64,
+    a_negative: bool,
+) -> f64 {
+    let mut x = 0.5 * (a + b);
+    for _ in 0..100 {
+        let f = evaluate(coeffs, x);
+        if f == 0.0 {
+            break;
+        }
+        if (f < 0.0) == a_negative {
+            a = x;
+        } else {
+            b = x;
+        }
+
+        let newton = x - f / evaluate(derivative, x);
+        let next = if newton > a && newton < b {
+            newton
+        } else {
+            0.5 * (a + b)
+        };
+        if (next - x).abs() <= 1e-14 * (1.0 + x.abs()) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    // Ring of radius 2 around the z axis, with a tube of radius 0.5
+    fn torus() -> Torus {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Torus::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            2.0,
+            0.5,
+            mat,
+        )
+    }
+
+    fn hit(origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        let r = Ray::new(origin, direction, 0.0);
+        torus()
+            .hit(&r, 0.001, common::INFINITY, &mut rec)
+            .then_some(rec)
+    }
+
+    // Distance from `p` to the surface
+    fn surface_distance(p: Point3) -> f64 {
+        let ring = f64::sqrt(p.x() * p.x() + p.y() * p.y()) - 2.0;
+        f64::sqrt(ring * ring + p.z() * p.z()) - 0.5
+    }
+
+    #[test]
+    fn nearest_of_four_roots() {
+        let rec = hit(Point3::new(5.0, 0.0, 0.0), Vec3::new(-2.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 1.25).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+
+        // Through the hole without touching the tube
+        assert!(hit(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+        assert!(hit(Point3::new(5.0, 5.0, 0.6), Vec3::new(-1.0, -1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn from_inside_the_tube() {
+        let inside = Point3::new(2.0, 0.0, 0.1);
+        let rec = hit(inside, Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!(!rec.front_face);
+        assert!(surface_distance(rec.p).abs() < 1e-9);
+        assert!(rec.p.x() > 2.0);
+        // The normal faces back into the tube, against the ray
+        assert!(rec.normal.x() < 0.0);
+
+        let rec = hit(inside, Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((rec.p.z() - 0.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn grazing_rays() {
+        // Tangent to the outer equator at (2.5, 0, 0), from just inside and
+        // just outside
+        let rec = hit(Point3::new(2.5 - 1e-7, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!(surface_distance(rec.p).abs() < 1e-6);
+        assert!(rec.p.y().abs() < 1e-2);
+        assert!(hit(Point3::new(2.5 + 1e-7, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).is_none());
+
+        // Skimming the top of the tube, which it touches at x = -2 and x = 2
+        let rec = hit(Point3::new(-5.0, 0.0, 0.5 - 1e-7), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!(surface_distance(rec.p).abs() < 1e-6);
+        assert!((rec.p.x() + 2.0).abs() < 1e-2);
+        assert!(hit(Point3::new(-5.0, 0.0, 0.5 + 1e-7), Vec3::new(1.0, 0.0, 0.0)).is_none());
+
+        // From far away, where the coefficients would be large without the
+        // restart at the closest point
+        let far = Point3::new(-1e6, 0.0, 0.49);
+        let rec = hit(far, Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!(surface_distance(rec.p).abs() < 1e-6);
+        assert!(rec.p.x() < -1.5);
+    }
+}
@@ -0,0 +1,872 @@
+use std::ops::Mul;
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::common::{self, Rng};
+use crate::hittable::{HitRecord, Hittable, Span};
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+/// 4x4 matrix of an affine transform, acting on column vectors
+#[derive(Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        Mat4::scaling(Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        let mut t = Mat4::identity();
+        for i in 0..3 {
+            t.m[i][3] = offset[i];
+        }
+        t
+    }
+
+    /// Scale by a different factor along each axis; negative factors mirror
+    pub fn scaling(factors: Vec3) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate().take(3) {
+            row[i] = factors[i];
+        }
+        m[3][3] = 1.0;
+        Mat4 { m }
+    }
+
+    /// Rotate counter-clockwise by `degrees` about `axis`, looking down the axis
+    pub fn rotation(axis: Vec3, degrees: f64) -> Mat4 {
+        let a = vec3::unit_vector(axis);
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let theta = common::degrees_to_radians(degrees);
+        let (s, c) = theta.sin_cos();
+        let t = 1.0 - c;
+
+        // Rodrigues' rotation formula
+        Mat4 {
+            m: [
+                [t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.0],
+                [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.0],
+                [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Mirror through the plane through the origin with the given normal
+    pub fn reflection(normal: Vec3) -> Mat4 {
+        let n = vec3::unit_vector(normal);
+        let mut r = Mat4::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                r.m[i][j] -= 2.0 * n[i] * n[j];
+            }
+        }
+        r
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut t = [[0.0; 4]; 4];
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4 { m: t }
+    }
+
+    /// Inverse matrix, or `None` if the matrix is singular
+    pub fn inverse(&self) -> Option<Mat4> {
+        // Gauss-Jordan elimination with partial pivoting
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Mat4 { m: inv })
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        Point3::new(
+            m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+            m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+            m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3],
+        )
+    }
+
+    /// Transform a direction, which is unaffected by translation
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    // `a * b` applies `b` first, then `a`
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+// The transforms that carry rays into an object's space and hits back out
+struct Placement {
+    // Object space to world space
+    matrix: Mat4,
+    inverse: Mat4,
+    // Transforms normals to world space
+    normal_matrix: Mat4,
+}
+
+impl Placement {
+    fn new(matrix: Mat4, inverse: Mat4) -> Placement {
+        Placement {
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        }
+    }
+
+    // The ray in object space; the direction is not normalized, so `t` means
+    // the same in both spaces
+    fn object_ray(&self, r: &Ray) -> Ray {
+        Ray::new(
+            self.inverse.transform_point(r.origin()),
+            self.inverse.transform_vector(r.direction()),
+            r.time(),
+        )
+    }
+
+    // Move a hit found in object space into the world
+    fn to_world(&self, rec: &mut HitRecord) {
+        rec.p = self.matrix.transform_point(rec.p);
+        rec.normal = vec3::unit_vector(self.normal_matrix.transform_vector(rec.normal));
+    }
+
+    // Hit the object, found by `hit_object` with the ray in object space
+    fn hit(
+        &self,
+        r: &Ray,
+        rec: &mut HitRecord,
+        hit_object: impl FnOnce(&Ray, &mut HitRecord) -> bool,
+    ) -> bool {
+        if !hit_object(&self.object_ray(r), rec) {
+            return false;
+        }
+
+        self.to_world(rec);
+        true
+    }
+
+    fn spans(&self, object: &dyn Hittable, r: &Ray, t_min: f64, t_max: f64) -> Vec<Span> {
+        let mut spans = object.spans(&self.object_ray(r), t_min, t_max);
+        for span in &mut spans {
+            self.to_world(&mut span.enter);
+            self.to_world(&mut span.exit);
+        }
+        spans
+    }
+}
+
+// Box holding all eight corners of `b` once transformed by `matrix`
+fn transformed_box(b: Aabb, matrix: &Mat4) -> Aabb {
+    let (lo, hi) = (b.min(), b.max());
+    let mut min = Point3::new(common::INFINITY, common::INFINITY, common::INFINITY);
+    let mut max = -min;
+    for corner in 0..8 {
+        let p = matrix.transform_point(Point3::new(
+            if corner & 1 == 0 { lo.x() } else { hi.x() },
+            if corner & 2 == 0 { lo.y() } else { hi.y() },
+            if corner & 4 == 0 { lo.z() } else { hi.z() },
+        ));
+        for axis in 0..3 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+    Aabb::new(min, max)
+}
+
+/// An object placed in the world by an affine transform
+///
+/// Rays are taken into the object's own space, so any hittable can be moved,
+/// rotated, scaled or mirrored, and one object can be shared by many instances.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    placement: Placement,
+    bbox: Option<Aabb>,
+}
+
+impl Instance {
+    /// Place `object` in the world with the given transform
+    ///
+    /// # Arguments
+    /// * `object` - The object, in its own coordinate space
+    /// * `matrix` - Transform from object space to world space
+    ///
+    /// # Panics
+    /// If `matrix` is singular, for example a scale by zero.
+    pub fn new(object: Arc<dyn Hittable>, matrix: Mat4) -> Instance {
+        let inverse = matrix
+            .inverse()
+            .expect("instance transform must be invertible");
+        let bbox = object.bounding_box().map(|b| transformed_box(b, &matrix));
+
+        Instance {
+            object,
+            placement: Placement::new(matrix, inverse),
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.placement
+            .hit(r, rec, |r, rec| self.object.hit(r, t_min, t_max, rec))
+    }
+
+    fn sample_hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut Rng,
+    ) -> bool {
+        self.placement.hit(r, rec, |r, rec| {
+            self.object.sample_hit(r, t_min, t_max, rec, rng)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    fn spans(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<Span> {
+        self.placement.spans(&*self.object, r, t_min, t_max)
+    }
+}
+
+/// A rotation, as a unit quaternion
+///
+/// Unlike matrices, quaternions can be interpolated (see `slerp`) into
+/// rotations that turn evenly from one orientation to another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Quaternion {
+    pub fn identity() -> Quaternion {
+        Quaternion {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    /// Rotate counter-clockwise by `degrees` about `axis`, like
+    /// `Mat4::rotation`
+    pub fn rotation(axis: Vec3, degrees: f64) -> Quaternion {
+        let a = vec3::unit_vector(axis);
+        let (s, c) = (common::degrees_to_radians(degrees) / 2.0).sin_cos();
+        Quaternion {
+            w: c,
+            x: s * a.x(),
+            y: s * a.y(),
+            z: s * a.z(),
+        }
+    }
+
+    /// Rotate by the given angles in degrees about the X, Y and Z axes in turn
+    pub fn euler(degrees: Vec3) -> Quaternion {
+        let x = Quaternion::rotation(Vec3::new(1.0, 0.0, 0.0), degrees.x());
+        let y = Quaternion::rotation(Vec3::new(0.0, 1.0, 0.0), degrees.y());
+        let z = Quaternion::rotation(Vec3::new(0.0, 0.0, 1.0), degrees.z());
+        z * y * x
+    }
+
+    fn dot(self, other: Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Rotation a fraction `f` of the way from this one to `other`, turning
+    /// the shorter way round at an even rate
+    pub fn slerp(self, other: Quaternion, f: f64) -> Quaternion {
+        // `q` and `-q` are the same rotation; pick the one nearer to `self`
+        let mut cos = self.dot(other);
+        let other = if cos < 0.0 {
+            cos = -cos;
+            Quaternion {
+                w: -other.w,
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+            }
+        } else {
+            other
+        };
+
+        // Nearly equal rotations interpolate linearly, avoiding 0 / 0
+        let (a, b) = if cos > 0.9995 {
+            (1.0 - f, f)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - f) * theta).sin() / sin, (f * theta).sin() / sin)
+        };
+        let q = Quaternion {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+        };
+        let norm = q.dot(q).sqrt();
+        Quaternion {
+            w: q.w / norm,
+            x: q.x / norm,
+            y: q.y / norm,
+            z: q.z / norm,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        let Quaternion { w, x, y, z } = *self;
+        Mat4 {
+            m: [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y - w * z),
+                    2.0 * (x * z + w * y),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * y + w * z),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z - w * x),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * z - w * y),
+                    2.0 * (y * z + w * x),
+                    1.0 - 2.0 * (x * x + y * y),
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    // `a * b` applies `b` first, then `a`
+    fn mul(self, o: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            x: self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            y: self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            z: self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        }
+    }
+}
+
+/// Pose of an animated object at one moment: scaled, then rotated, then
+/// translated
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(time: f64, translation: Vec3, rotation: Quaternion, scale: Vec3) -> Keyframe {
+        Keyframe {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    /// Object space to world space
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translation(self.translation) * self.rotation.matrix() * Mat4::scaling(self.scale)
+    }
+
+    // Inverse of `matrix`, undoing each step in reverse order
+    fn inverse(&self) -> Mat4 {
+        let s = self.scale;
+        Mat4::scaling(Vec3::new(1.0 / s.x(), 1.0 / s.y(), 1.0 / s.z()))
+            * self.rotation.matrix().transpose()
+            * Mat4::translation(-self.translation)
+    }
+
+    // Pose a fraction `f` of the way from this keyframe to `next`
+    fn lerp(&self, next: &Keyframe, f: f64) -> Keyframe {
+        Keyframe {
+            time: self.time + f * (next.time - self.time),
+            translation: self.translation + f * (next.translation - self.translation),
+            rotation: self.rotation.slerp(next.rotation, f),
+            scale: self.scale + f * (next.scale - self.scale),
+        }
+    }
+}
+
+/// An object whose transform is keyframed over time, for motion blur
+///
+/// Each ray sees the object posed at the ray's time: translation and scale
+/// are interpolated linearly between keyframes and rotation by `slerp`.
+/// Before the first keyframe and after the last the object holds still.
+pub struct AnimatedInstance {
+    object: Arc<dyn Hittable>,
+    keyframes: Vec<Keyframe>,
+    bbox: Option<Aabb>,
+}
+
+impl AnimatedInstance {
+    /// Animate `object` through the given poses
+    ///
+    /// # Arguments
+    /// * `object` - The object, in its own coordinate space
+    /// * `keyframes` - Its poses, in any order
+    ///
+    /// # Panics
+    /// If there are no keyframes, or one scales by zero.
+    pub fn new(object: Arc<dyn Hittable>, mut keyframes: Vec<Keyframe>) -> AnimatedInstance {
+        assert!(!keyframes.is_empty(), "an animation needs a keyframe");
+        assert!(
+            keyframes
+                .iter()
+                .all(|k| k.scale.x() * k.scale.y() * k.scale.z() != 0.0),
+            "keyframe scale must not be zero"
+        );
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let bbox = object.bounding_box().map(|b| {
+            let mut bbox = transformed_box(b, &keyframes[0].matrix());
+            for pair in keyframes.windows(2) {
+                bbox = aabb::surrounding_box(bbox, motion_box(b, &pair[0], &pair[1]));
+            }
+            bbox
+        });
+
+        AnimatedInstance {
+            object,
+            keyframes,
+            bbox,
+        }
+    }
+
+    /// Pose of the object at the given time
+    pub fn pose(&self, time: f64) -> Keyframe {
+        let keys = &self.keyframes;
+        let next = keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return keys[0];
+        }
+        if next == keys.len() {
+            return keys[next - 1];
+        }
+        let (a, b) = (&keys[next - 1], &keys[next]);
+        a.lerp(b, (time - a.time) / (b.time - a.time))
+    }
+
+    fn placement(&self, time: f64) -> Placement {
+        let pose = self.pose(time);
+        Placement::new(pose.matrix(), pose.inverse())
+    }
+}
+
+// Box holding an object with bounding box `b` throughout the motion between
+// two keyframes
+fn motion_box(b: Aabb, from: &Keyframe, to: &Keyframe) -> Aabb {
+    if from.rotation.dot(to.rotation).abs() >= 1.0 - 1e-12 {
+        // Without rotation every corner moves in a straight line
+        return aabb::surrounding_box(
+            transformed_box(b, &from.matrix()),
+            transformed_box(b, &to.matrix()),
+        );
+    }
+
+    // A rotating object stays within the sphere around its origin reaching
+    // its farthest corner; scaling is linear, so that is farthest at one end
+    let mut reach: f64 = 0.0;
+    let (lo, hi) = (b.min(), b.max());
+    for scale in [from.scale, to.scale] {
+        for corner in 0..8 {
+            let p = Vec3::new(
+                scale.x() * if corner & 1 == 0 { lo.x() } else { hi.x() },
+                scale.y() * if corner & 2 == 0 { lo.y() } else { hi.y() },
+                scale.z() * if corner & 4 == 0 { lo.z() } else { hi.z() },
+            );
+            reach = reach.max(p.length());
+        }
+    }
+    let r = Vec3::new(reach, reach, reach);
+    aabb::surrounding_box(
+        Aabb::new(from.translation - r, from.translation + r),
+        Aabb::new(to.translation - r, to.translation + r),
+    )
+}
+
+impl Hittable for AnimatedInstance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.placement(r.time())
+            .hit(r, rec, |r, rec| self.object.hit(r, t_min, t_max, rec))
+    }
+
+    fn sample_hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut Rng,
+    ) -> bool {
+        self.placement(r.time()).hit(r, rec, |r, rec| {
+            self.object.sample_hit(r, t_min, t_max, rec, rng)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    fn spans(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<Span> {
+        self.placement(r.time())
+            .spans(&*self.object, r, t_min, t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::cube::Cube;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    fn assert_matrix_near(a: &Mat4, b: &Mat4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!(
+                    (a.m[i][j] - b.m[i][j]).abs() < 1e-9,
+                    "{:?} != {:?}",
+                    a.m,
+                    b.m
+                );
+            }
+        }
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    fn hit(object: &dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        let r = Ray::new(origin, direction, 0.0);
+        object
+            .hit(&r, 0.001, common::INFINITY, &mut rec)
+            .then_some(rec)
+    }
+
+    fn unit_sphere(center: Point3) -> Arc<dyn Hittable> {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Arc::new(Sphere::new(center, 1.0, mat))
+    }
+
+    #[test]
+    fn inverse_undoes_matrix() {
+        let m = Mat4::translation(Vec3::new(1.0, -2.0, 3.0))
+            * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 33.0)
+            * Mat4::scaling(Vec3::new(2.0, -3.0, 0.5))
+            * Mat4::reflection(Vec3::new(0.0, 1.0, 1.0));
+        let inverse = m.inverse().unwrap();
+        assert_matrix_near(&(m * inverse), &Mat4::identity());
+        assert_matrix_near(&(inverse * m), &Mat4::identity());
+
+        assert!(Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+        // A rotation's inverse is its transpose
+        let r = Mat4::rotation(Vec3::new(0.3, -1.0, 2.0), 71.0);
+        assert_matrix_near(&r.inverse().unwrap(), &r.transpose());
+    }
+
+    #[test]
+    fn composition_applies_right_first() {
+        let t = Mat4::translation(Vec3::new(1.0, 0.0, 0.0));
+        let r = Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let p = Point3::new(1.0, 0.0, 0.0);
+        // Turned to (0, 1, 0), then moved
+        assert_near((t * r).transform_point(p), Point3::new(1.0, 1.0, 0.0));
+        // Moved to (2, 0, 0), then turned
+        assert_near((r * t).transform_point(p), Point3::new(0.0, 2.0, 0.0));
+        // Directions ignore translation
+        assert_near((t * r).transform_vector(p), Vec3::new(0.0, 1.0, 0.0));
+
+        let mirror = Mat4::reflection(Vec3::new(1.0, 1.0, 0.0));
+        assert_near(mirror.transform_point(p), Point3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn scaled_sphere_normals() {
+        // An ellipsoid with semi-axes 2, 1, 1 centered on (5, 0, 0)
+        let matrix =
+            Mat4::translation(Vec3::new(5.0, 0.0, 0.0)) * Mat4::scaling(Vec3::new(2.0, 1.0, 1.0));
+        let ellipsoid = Instance::new(unit_sphere(Point3::new(0.0, 0.0, 0.0)), matrix);
+
+        let rec = hit(
+            &ellipsoid,
+            Point3::new(10.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(1.0, 0.0, 0.0));
+        let rec = hit(
+            &ellipsoid,
+            Point3::new(5.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        // Elsewhere the normal is the gradient of x²/4 + y² + z², which the
+        // scaled normal of the sphere would get wrong
+        let rec = hit(
+            &ellipsoid,
+            Point3::new(6.0, 5.0, 0.3),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        let q = rec.p - Point3::new(5.0, 0.0, 0.0);
+        assert!((q.x() * q.x() / 4.0 + q.y() * q.y() + q.z() * q.z() - 1.0).abs() < 1e-9);
+        assert_near(
+            rec.normal,
+            vec3::unit_vector(Vec3::new(q.x() / 4.0, q.y(), q.z())),
+        );
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn mirrored_sphere_keeps_outward_normals() {
+        // The sphere around (1, 0, 0) mirrored to (-1, 0, 0)
+        let mirrored = Instance::new(
+            unit_sphere(Point3::new(1.0, 0.0, 0.0)),
+            Mat4::scaling(Vec3::new(-1.0, 1.0, 1.0)),
+        );
+        let rec = hit(
+            &mirrored,
+            Point3::new(-5.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert_near(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+
+        // From inside, the normal faces back in
+        let rec = hit(
+            &mirrored,
+            Point3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        )
+        .unwrap();
+        assert!(!rec.front_face);
+        assert_near(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!(hit(
+            &mirrored,
+            Point3::new(1.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn instance_bounding_box() {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let cube = Arc::new(Cube::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            mat,
+        ));
+        // Turned 45 degrees about y, the cube reaches sqrt(2) along x and z
+        let matrix = Mat4::translation(Vec3::new(0.0, 3.0, 0.0))
+            * Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), 45.0);
+        let bbox = Instance::new(cube, matrix).bounding_box().unwrap();
+        let s = f64::sqrt(2.0);
+        assert!((bbox.min() - Point3::new(-s, 2.0, -s)).length() < 1e-3);
+        assert!((bbox.max() - Point3::new(s, 4.0, s)).length() < 1e-3);
+    }
+
+    fn assert_quaternion_near(a: Quaternion, b: Quaternion) {
+        // `q` and `-q` are the same rotation
+        assert!(a.dot(b).abs() > 1.0 - 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn encloses(outer: Aabb, inner: Aabb) -> bool {
+        (0..3).all(|axis| {
+            outer.min()[axis] <= inner.min()[axis] + 1e-9
+                && inner.max()[axis] <= outer.max()[axis] + 1e-9
+        })
+    }
+
+    #[test]
+    fn slerp_turns_evenly() {
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        let a = Quaternion::rotation(z, 10.0);
+        let b = Quaternion::rotation(z, 130.0);
+        assert_quaternion_near(a.slerp(b, 0.0), a);
+        assert_quaternion_near(a.slerp(b, 1.0), b);
+        assert_quaternion_near(a.slerp(b, 0.5), Quaternion::rotation(z, 70.0));
+        assert_quaternion_near(a.slerp(b, 0.25), Quaternion::rotation(z, 40.0));
+
+        // 350 degrees is reached by turning 20 degrees back, not 340 forward
+        let c = Quaternion::rotation(z, 350.0);
+        assert_quaternion_near(a.slerp(c, 0.5), Quaternion::identity());
+
+        // The matrix of a quaternion matches the rotation matrix
+        let axis = Vec3::new(1.0, -2.0, 0.5);
+        assert_matrix_near(
+            &Quaternion::rotation(axis, 63.0).matrix(),
+            &Mat4::rotation(axis, 63.0),
+        );
+    }
+
+    fn animated_cube() -> (Aabb, AnimatedInstance) {
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        // Off the origin, so rotation swings it around
+        let cube = Arc::new(Cube::new(
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(2.0, 0.5, 1.0),
+            mat,
+        ));
+        let b = cube.bounding_box().unwrap();
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let keyframes = vec![
+            Keyframe::new(
+                1.0,
+                Vec3::new(3.0, 0.0, 0.0),
+                Quaternion::rotation(y, 90.0),
+                Vec3::new(2.0, 2.0, 2.0),
+            ),
+            Keyframe::new(
+                0.0,
+                Vec3::new(0.0, 0.0, 0.0),
+                Quaternion::identity(),
+                Vec3::new(1.0, 1.0, 1.0),
+            ),
+            Keyframe::new(
+                2.0,
+                Vec3::new(3.0, 4.0, 0.0),
+                Quaternion::rotation(y, 90.0),
+                Vec3::new(1.0, 0.5, 1.0),
+            ),
+        ];
+        (b, AnimatedInstance::new(cube, keyframes))
+    }
+
+    #[test]
+    fn pose_matches_keyframes_and_holds_outside_them() {
+        let (_, animated) = animated_cube();
+        for key in animated.keyframes.clone() {
+            let pose = animated.pose(key.time);
+            assert_near(pose.translation, key.translation);
+            assert_near(pose.scale, key.scale);
+            assert_quaternion_near(pose.rotation, key.rotation);
+            assert_matrix_near(&pose.matrix(), &key.matrix());
+        }
+
+        let first = animated.pose(0.0);
+        let before = animated.pose(-5.0);
+        assert_matrix_near(&before.matrix(), &first.matrix());
+        let last = animated.pose(2.0);
+        let after = animated.pose(7.0);
+        assert_matrix_near(&after.matrix(), &last.matrix());
+
+        // Halfway between the first two keyframes
+        let mid = animated.pose(0.5);
+        assert_near(mid.translation, Vec3::new(1.5, 0.0, 0.0));
+        assert_near(mid.scale, Vec3::new(1.5, 1.5, 1.5));
+        assert_quaternion_near(
+            mid.rotation,
+            Quaternion::rotation(Vec3::new(0.0, 1.0, 0.0), 45.0),
+        );
+
+        // The inverse undoes the pose
+        assert_matrix_near(&(mid.matrix() * mid.inverse()), &Mat4::identity());
+    }
+
+    #[test]
+    fn animated_box_encloses_every_pose() {
+        let (b, animated) = animated_cube();
+        let bbox = animated.bounding_box().unwrap();
+        for i in -10..=30 {
+            let pose = animated.pose(i as f64 / 10.0);
+            let posed = transformed_box(b, &pose.matrix());
+            assert!(encloses(bbox, posed), "pose at time {} escapes", i);
+        }
+    }
+
+    #[test]
+    fn animated_instance_hits_posed_object() {
+        let (_, animated) = animated_cube();
+        // At time 2 the cube is scaled to [1, 2] x [0, 0.25] x [0, 1], turned
+        // to x in [0, 1], z in [-2, -1], then moved by (3, 4, 0)
+        let r = Ray::new(Point3::new(3.5, 10.0, -1.5), Vec3::new(0.0, -1.0, 0.0), 2.0);
+        let mut rec = HitRecord::new();
+        assert!(animated.hit(&r, 0.001, common::INFINITY, &mut rec));
+        assert!((rec.t - 5.75).abs() < 1e-9);
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        // At time 0 it is still at the origin
+        let r = Ray::new(Point3::new(3.5, 10.0, -1.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(!animated.hit(&r, 0.001, common::INFINITY, &mut rec));
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use crate::aabb::{self, Aabb};
+use crate::common::{self, Rng};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::plane;
+use crate::ray::Ray;
+use crate::vec3::{self, Point3, Vec3};
+
+pub struct Triangle {
+    vertices: [Point3; 3],
+    mat: Arc<dyn Material>,
+}
+
+impl Triangle {
+    /// Create a flat-shaded triangle
+    ///
+    /// # Arguments
+    /// * `a`, `b`, `c` - The corners; seen from the front they wind counter-clockwise
+    /// * `mat` - The material of the triangle
+    pub fn new(a: Point3, b: Point3, c: Point3, mat: Arc<dyn Material>) -> Triangle {
+        Triangle {
+            vertices: [a, b, c],
+            mat,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let [a, b, c] = self.vertices;
+        let Some((t, barycentric)) = intersect(r, a, b, c, t_min, t_max) else {
+            return false;
+        };
+
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.u = barycentric[1];
+        rec.v = barycentric[2];
+        rec.set_face_normal(r, vec3::unit_vector(vec3::cross(b - a, c - a)));
+        rec.mat = Some(self.mat.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [a, b, c] = self.vertices;
+        Some(aabb::surrounding_box(Aabb::new(a, b), Aabb::new(c, c)))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let [a, b, c] = self.vertices;
+        let area = 0.5 * vec3::cross(b - a, c - a).length();
+        plane::solid_angle_pdf(self, area, origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let [a, b, c] = self.vertices;
+        let mut s = common::random_double(rng);
+        let mut t = common::random_double(rng);
+        // Fold points from the far half of the parallelogram back into the triangle
+        if s + t > 1.0 {
+            (s, t) = (1.0 - s, 1.0 - t);
+        }
+        a + s * (b - a) + t * (c - a) - origin
+    }
+}
+
+/// Watertight ray/triangle intersection (Woop, Benthin and Wald, 2013)
+///
+/// Returns the ray parameter and the barycentric weights of `a`, `b` and `c`.
+/// Rays through a shared edge or vertex always hit at least one of the
+/// triangles that meet there, so meshes show no cracks.
+pub fn intersect(
+    r: &Ray,
+    a: Point3,
+    b: Point3,
+    c: Point3,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, [f64; 3])> {
+    let dir = r.direction();
+
+    // Permute the axes so that the ray travels mostly along +z
+    let kz = (0..3)
+        .max_by(|&i, &j| dir[i].abs().total_cmp(&dir[j].abs()))
+        .unwrap();
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if dir[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // Shear so the ray becomes the +z axis through the origin
+    let sx = dir[kx] / dir[kz];
+    let sy = dir[ky] / dir[kz];
+    let sz = 1.0 / dir[kz];
+    let a = a - r.origin();
+    let b = b - r.origin();
+    let c = c - r.origin();
+    let (ax, ay) = (a[kx] - sx * a[kz], a[ky] - sy * a[kz]);
+    let (bx, by) = (b[kx] - sx * b[kz], b[ky] - sy * b[kz]);
+    let (cx, cy) = (c[kx] - sx * c[kz], c[ky] - sy * c[kz]);
+
+    // Scaled barycentric coordinates, from edge functions in the sheared plane
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t_scaled = u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz];
+    let t = t_scaled / det;
+    if t <= t_min || t_max <= t {
+        return None;
+    }
+
+    Some((t, [u / det, v / det, w / det]))
+}
@@ -1,159 +1,175 @@
 use std::fmt::{Display, Formatter, Result};
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub};
- 
-use crate::common;
- 
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub};
+
+use crate::common::{self, Rng};
+
 #[derive(Copy, Clone, Default)]
 pub struct Vec3 {
     e: [f64; 3],
 }
- 
+
 impl Vec3 {
     pub fn new(x: f64, y: f64, z: f64) -> Vec3 {
         Vec3 { e: [x, y, z] }
     }
- 
-    pub fn random() -> Vec3 {
+
+    pub fn random(rng: &mut Rng) -> Vec3 {
         Vec3::new(
-            common::random_double(),
-            common::random_double(),
-            common::random_double(),
+            common::random_double(rng),
+            common::random_double(rng),
+            common::random_double(rng),
         )
     }
- 
-    pub fn random_range(min: f64, max: f64) -> Vec3 {
+
+    pub fn random_range(rng: &mut Rng, min: f64, max: f64) -> Vec3 {
         Vec3::new(
-            common::random_double_range(min, max),
-            common::random_double_range(min, max),
-            common::random_double_range(min, max),
+            common::random_double_range(rng, min, max),
+            common::random_double_range(rng, min, max),
+            common::random_double_range(rng, min, max),
         )
     }
- 
+
     pub fn x(&self) -> f64 {
         self.e[0]
     }
- 
+
     pub fn y(&self) -> f64 {
         self.e[1]
     }
- 
+
     pub fn z(&self) -> f64 {
         self.e[2]
     }
- 
+
     pub fn length(&self) -> f64 {
         f64::sqrt(self.length_squared())
     }
- 
+
     pub fn length_squared(&self) -> f64 {
         self.e[0] * self.e[0] + self.e[1] * self.e[1] + self.e[2] * self.e[2]
     }
- 
+
     pub fn near_zero(&self) -> bool {
         const EPS: f64 = 1.0e-8;
         // Return true if the vector is close to zero in all dimensions
         self.e[0].abs() < EPS && self.e[1].abs() < EPS && self.e[2].abs() < EPS
     }
 }
- 
+
 // Type alias
 pub type Point3 = Vec3;
- 
+
 // Output formatting
 impl Display for Vec3 {
     fn fmt(&self, f: &mut Formatter) -> Result {
         write!(f, "{} {} {}", self.e[0], self.e[1], self.e[2])
     }
 }
- 
+
 // -Vec3
 impl Neg for Vec3 {
     type Output = Vec3;
- 
+
     fn neg(self) -> Vec3 {
         Vec3::new(-self.x(), -self.y(), -self.z())
     }
 }
- 
+
+// Vec3[i]
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &f64 {
+        &self.e[i]
+    }
+}
+
+// Vec3[i] = f64
+impl IndexMut<usize> for Vec3 {
+    fn index_mut(&mut self, i: usize) -> &mut f64 {
+        &mut self.e[i]
+    }
+}
+
 // Vec3 += Vec3
 impl AddAssign for Vec3 {
     fn add_assign(&mut self, v: Vec3) {
         *self = *self + v;
     }
 }
- 
+
 // Vec3 *= f64
 impl MulAssign<f64> for Vec3 {
     fn mul_assign(&mut self, t: f64) {
         *self = *self * t;
     }
 }
- 
+
 // Vec3 /= f64
 impl DivAssign<f64> for Vec3 {
     fn div_assign(&mut self, t: f64) {
         *self = *self / t;
     }
 }
- 
+
 // Vec3 + Vec3
 impl Add for Vec3 {
     type Output = Vec3;
- 
+
     fn add(self, v: Vec3) -> Vec3 {
         Vec3::new(self.x() + v.x(), self.y() + v.y(), self.z() + v.z())
     }
 }
- 
+
 // Vec3 - Vec3
 impl Sub for Vec3 {
     type Output = Vec3;
- 
+
     fn sub(self, v: Vec3) -> Vec3 {
         Vec3::new(self.x() - v.x(), self.y() - v.y(), self.z() - v.z())
     }
 }
- 
+
 // Vec3 * Vec3
 impl Mul for Vec3 {
     type Output = Vec3;
- 
+
     fn mul(self, v: Vec3) -> Vec3 {
         Vec3::new(self.x() * v.x(), self.y() * v.y(), self.z() * v.z())
     }
 }
- 
+
 // f64 * Vec3
 impl Mul<Vec3> for f64 {
     type Output = Vec3;
- 
+
     fn mul(self, v: Vec3) -> Vec3 {
         Vec3::new(self * v.x(), self * v.y(), self * v.z())
     }
 }
- 
+
 // Vec3 * f64
 impl Mul<f64> for Vec3 {
     type Output = Vec3;
- 
+
     fn mul(self, t: f64) -> Vec3 {
         Vec3::new(self.x() * t, self.y() * t, self.z() * t)
     }
 }
- 
+
 // Vec3 / f64
 impl Div<f64> for Vec3 {
     type Output = Vec3;
- 
+
     fn div(self, t: f64) -> Vec3 {
         Vec3::new(self.x() / t, self.y() / t, self.z() / t)
     }
 }
- 
+
 pub fn dot(u: Vec3, v: Vec3) -> f64 {
     u.e[0] * v.e[0] + u.e[1] * v.e[1] + u.e[2] * v.e[2]
 }
- 
+
 pub fn cross(u: Vec3, v: Vec3) -> Vec3 {
     Vec3::new(
         u.e[1] * v.e[2] - u.e[2] * v.e[1],
@@ -161,32 +177,58 @@ pub fn cross(u: Vec3, v: Vec3) -> Vec3 {
         u.e[0] * v.e[1] - u.e[1] * v.e[0],
     )
 }
- 
+
 pub fn unit_vector(v: Vec3) -> Vec3 {
     v / v.length()
 }
- 
-pub fn random_in_unit_sphere() -> Vec3 {
+
+pub fn random_in_unit_sphere(rng: &mut Rng) -> Vec3 {
     loop {
-        let p = Vec3::random_range(-1.0, 1.0);
+        let p = Vec3::random_range(rng, -1.0, 1.0);
         if p.length_squared() >= 1.0 {
             continue;
         }
         return p;
     }
 }
- 
-pub fn random_unit_vector() -> Vec3 {
-    unit_vector(random_in_unit_sphere())
+
+/// Random point in the disk of radius 1 in the XY plane
+pub fn random_in_unit_disk(rng: &mut Rng) -> Vec3 {
+    loop {
+        let p = Vec3::new(
+            common::random_double_range(rng, -1.0, 1.0),
+            common::random_double_range(rng, -1.0, 1.0),
+            0.0,
+        );
+        if p.length_squared() < 1.0 {
+            return p;
+        }
+    }
+}
+
+pub fn random_unit_vector(rng: &mut Rng) -> Vec3 {
+    unit_vector(random_in_unit_sphere(rng))
 }
- 
+
+/// Random direction about the +Z axis, with density proportional to cos(theta)
+pub fn random_cosine_direction(rng: &mut Rng) -> Vec3 {
+    let r1 = common::random_double(rng);
+    let r2 = common::random_double(rng);
+
+    let phi = 2.0 * common::PI * r1;
+    let x = f64::cos(phi) * f64::sqrt(r2);
+    let y = f64::sin(phi) * f64::sqrt(r2);
+    let z = f64::sqrt(1.0 - r2);
+    Vec3::new(x, y, z)
+}
+
 pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
     v - 2.0 * dot(v, n) * n
 }
- 
+
 pub fn refract(uv: Vec3, n: Vec3, etai_over_etat: f64) -> Vec3 {
     let cos_theta = f64::min(dot(-uv, n), 1.0);
     let r_out_perp = etai_over_etat * (uv + cos_theta * n);
     let r_out_parallel = -f64::sqrt(f64::abs(1.0 - r_out_perp.length_squared())) * n;
     r_out_perp + r_out_parallel
-}
\ No newline at end of file
+}
@@ -0,0 +1,462 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::aabb::Aabb;
+use crate::vec3::{Point3, Vec3};
+
+// Voxel grid files are text, with a header followed by the voxel values:
+//
+//   # comment
+//   size 64 32 64                  voxels along x, y and z (required)
+//   channels density temperature   what each voxel holds, in order: `density`
+//                                  (required), `emission`, `temperature`
+//                                  (default: density alone)
+//   origin 0 0 0                   minimum corner of the grid (default 0 0 0)
+//   voxel_size 0.1                 edge length of each voxel (default 1)
+//   data                           the rest of the file is voxel values
+//   0 0.25 0 0.5 ...
+//
+// Each voxel gives one number per channel. Voxels are listed with x varying
+// fastest, then y, then z, and the numbers may be split across lines in any
+// way. Density is per unit distance and never negative, emission a brightness
+// and temperature in kelvin.
+
+#[derive(Debug)]
+pub enum VoxelError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for VoxelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoxelError::Io(err) => write!(f, "cannot read voxel grid: {}", err),
+            VoxelError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for VoxelError {}
+
+impl From<io::Error> for VoxelError {
+    fn from(err: io::Error) -> VoxelError {
+        VoxelError::Io(err)
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> VoxelError {
+    VoxelError::Parse {
+        line,
+        message: message.into(),
+    }
+}
+
+/// A box of voxels holding density and, optionally, emission and temperature
+///
+/// Values are looked up between voxel centers by trilinear interpolation, and
+/// are zero outside the grid.
+pub struct VoxelGrid {
+    size: [usize; 3],
+    origin: Point3,
+    voxel_size: f64,
+    density: Vec<f32>,
+    emission: Option<Vec<f32>>,
+    temperature: Option<Vec<f32>>,
+    max_density: f64,
+}
+
+impl VoxelGrid {
+    /// Create a grid of densities
+    ///
+    /// # Arguments
+    /// * `size` - The number of voxels along x, y and z
+    /// * `origin` - The minimum corner of the grid
+    /// * `voxel_size` - The edge length of each voxel
+    /// * `density` - One value per voxel, x varying fastest, then y, then z
+    ///
+    /// # Panics
+    /// If there is not one density per voxel, or a density is negative.
+    pub fn new(size: [usize; 3], origin: Point3, voxel_size: f64, density: Vec<f32>) -> VoxelGrid {
+        assert_eq!(Some(density.len()), voxel_count(size));
+        assert!(
+            density.iter().all(|&d| d >= 0.0),
+            "densities must not be negative"
+        );
+        let max_density = density.iter().fold(0.0f32, |m, &d| m.max(d)) as f64;
+        VoxelGrid {
+            size,
+            origin,
+            voxel_size,
+            density,
+            emission: None,
+            temperature: None,
+            max_density,
+        }
+    }
+
+    /// Add an emission channel, laid out like the densities
+    pub fn with_emission(mut self, emission: Vec<f32>) -> VoxelGrid {
+        assert_eq!(emission.len(), self.density.len());
+        self.emission = Some(emission);
+        self
+    }
+
+    /// Add a temperature channel in kelvin, laid out like the densities
+    pub fn with_temperature(mut self, temperature: Vec<f32>) -> VoxelGrid {
+        assert_eq!(temperature.len(), self.density.len());
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Box the grid fills
+    pub fn bounds(&self) -> Aabb {
+        let extent = Vec3::new(
+            self.size[0] as f64,
+            self.size[1] as f64,
+            self.size[2] as f64,
+        );
+        Aabb::new(self.origin, self.origin + self.voxel_size * extent)
+    }
+
+    /// Largest density in the grid
+    pub fn max_density(&self) -> f64 {
+        self.max_density
+    }
+
+    pub fn has_emission(&self) -> bool {
+        self.emission.is_some()
+    }
+
+    pub fn has_temperature(&self) -> bool {
+        self.temperature.is_some()
+    }
+
+    /// Density at `p`
+    pub fn density(&self, p: Point3) -> f64 {
+        self.lookup(&self.density, p)
+    }
+
+    /// Emission at `p`, zero if the grid has no emission channel
+    pub fn emission(&self, p: Point3) -> f64 {
+        self.emission.as_ref().map_or(0.0, |e| self.lookup(e, p))
+    }
+
+    /// Temperature at `p`, zero if the grid has no temperature channel
+    pub fn temperature(&self, p: Point3) -> f64 {
+        self.temperature.as_ref().map_or(0.0, |t| self.lookup(t, p))
+    }
+
+    // Trilinear interpolation of a channel between the eight voxel centers
+    // around `p`, holding the outermost values out to the edge of the grid
+    fn lookup(&self, values: &[f32], p: Point3) -> f64 {
+        let mut index = [[0; 2]; 3];
+        let mut weight = [0.0; 3];
+        for axis in 0..3 {
+            let n = self.size[axis];
+            let x = (p[axis] - self.origin[axis]) / self.voxel_size;
+            if !(0.0..=n as f64).contains(&x) {
+                return 0.0;
+            }
+            let x = (x - 0.5).clamp(0.0, (n - 1) as f64);
+            let i = (x as usize).min(n - 1);
+            index[axis] = [i, (i + 1).min(n - 1)];
+            weight[axis] = x - i as f64;
+        }
+
+        let [nx, ny, _] = self.size;
+        let mut value = 0.0;
+        for corner in 0..8 {
+            let mut w = 1.0;
+            let mut offset = 0;
+            for (axis, stride) in [1, nx, nx * ny].into_iter().enumerate() {
+                let upper = (corner >> axis) & 1;
+                w *= if upper == 1 {
+                    weight[axis]
+                } else {
+                    1.0 - weight[axis]
+                };
+                offset += index[axis][upper] * stride;
+            }
+            value += w * values[offset] as f64;
+        }
+        value
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Channel {
+    Density,
+    Emission,
+    Temperature,
+}
+
+/// Load a voxel grid from a file on disk
+pub fn load(path: impl AsRef<Path>) -> Result<VoxelGrid, VoxelError> {
+    let text = fs::read_to_string(path)?;
+    parse(&text)
+}
+
+/// Build a voxel grid from the text of a voxel grid file
+pub fn parse(text: &str) -> Result<VoxelGrid, VoxelError> {
+    let mut size = None;
+    let mut count = 0;
+    let mut channels = vec![Channel::Density];
+    let mut origin = Point3::new(0.0, 0.0, 0.0);
+    let mut voxel_size = 1.0;
+    let mut values: Option<Vec<f32>> = None;
+    let mut last_line = 0;
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        last_line = line;
+        let content = raw.split('#').next().unwrap_or("");
+        let mut tokens = content.split_whitespace();
+
+        if let Some(values) = values.as_mut() {
+            for token in tokens {
+                let value = number(line, token)?;
+                let is_density = channels[values.len() % channels.len()] == Channel::Density;
+                if is_density && value < 0.0 {
+                    return Err(error(line, format!("negative density `{}`", token)));
+                }
+                values.push(value as f32);
+            }
+            continue;
+        }
+
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+        match keyword {
+            "size" => {
+                let n = numbers(line, keyword, &args, 3)?;
+                if n.iter().any(|&x| x < 1.0 || x.fract() != 0.0) {
+                    return Err(error(
+                        line,
+                        "`size` needs three whole numbers of at least 1",
+                    ));
+                }
+                let n = [n[0] as usize, n[1] as usize, n[2] as usize];
+                count = voxel_count(n).ok_or_else(|| error(line, "`size` has too many voxels"))?;
+                size = Some(n);
+            }
+            "channels" => {
+                channels = args
+                    .iter()
+                    .map(|&name| match name {
+                        "density" => Ok(Channel::Density),
+                        "emission" => Ok(Channel::Emission),
+                        "temperature" => Ok(Channel::Temperature),
+                        _ => Err(error(line, format!("unknown channel `{}`", name))),
+                    })
+                    .collect::<Result<_, _>>()?;
+                for (i, channel) in channels.iter().enumerate() {
+                    if channels[..i].contains(channel) {
+                        return Err(error(line, "a channel is listed twice"));
+                    }
+                }
+                if !channels.contains(&Channel::Density) {
+                    return Err(error(line, "the `density` channel is required"));
+                }
+            }
+            "origin" => {
+                let n = numbers(line, keyword, &args, 3)?;
+                origin = Point3::new(n[0], n[1], n[2]);
+            }
+            "voxel_size" => {
+                voxel_size = numbers(line, keyword, &args, 1)?[0];
+                if voxel_size <= 0.0 {
+                    return Err(error(line, "`voxel_size` must be positive"));
+                }
+            }
+            "data" => {
+                if size.is_none() {
+                    return Err(error(line, "`size` must come before `data`"));
+                }
+                values = Some(Vec::new());
+            }
+            _ => return Err(error(line, format!("unknown keyword `{}`", keyword))),
+        }
+    }
+
+    let (Some(size), Some(values)) = (size, values) else {
+        return Err(error(last_line, "missing `size` or `data`"));
+    };
+    let Some(expected) = count.checked_mul(channels.len()) else {
+        return Err(error(last_line, "the grid has too many values"));
+    };
+    if values.len() != expected {
+        return Err(error(
+            last_line,
+            format!(
+                "expected {} values ({} voxels with {} channels), found {}",
+                expected,
+                count,
+                channels.len(),
+                values.len()
+            ),
+        ));
+    }
+
+    // Split the interleaved values into one list per channel
+    let channel = |c: Channel| {
+        let k = channels.iter().position(|&x| x == c)?;
+        Some(
+            values
+                .iter()
+                .skip(k)
+                .step_by(channels.len())
+                .copied()
+                .collect::<Vec<f32>>(),
+        )
+    };
+    let mut grid = VoxelGrid::new(size, origin, voxel_size, channel(Channel::Density).unwrap());
+    if let Some(emission) = channel(Channel::Emission) {
+        grid = grid.with_emission(emission);
+    }
+    if let Some(temperature) = channel(Channel::Temperature) {
+        grid = grid.with_temperature(temperature);
+    }
+    Ok(grid)
+}
+
+// Number of voxels in a grid of the given size, `None` if it overflows
+fn voxel_count(size: [usize; 3]) -> Option<usize> {
+    size.iter().try_fold(1usize, |n, &s| n.checked_mul(s))
+}
+
+// Parse exactly `count` numbers
+fn numbers(
+    line: usize,
+    keyword: &str,
+    args: &[&str],
+    count: usize,
+) -> Result<Vec<f64>, VoxelError> {
+    if args.len() != count {
+        return Err(error(
+            line,
+            format!("`{}` needs {} numbers", keyword, count),
+        ));
+    }
+    args.iter().map(|arg| number(line, arg)).collect()
+}
+
+fn number(line: usize, s: &str) -> Result<f64, VoxelError> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .ok_or_else(|| error(line, format!("invalid number `{}`", s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_line(text: &str) -> usize {
+        match parse(text) {
+            Err(VoxelError::Parse { line, .. }) => line,
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("grid parsed"),
+        }
+    }
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn channels_are_interleaved() {
+        let text = "\
+# two voxels along x
+size 2 1 1
+channels temperature density emission
+origin 1 0 0
+voxel_size 0.5
+data
+1000 0.25 3
+2000 0.75
+4
+";
+        let grid = parse(text).unwrap();
+        assert!(grid.has_emission() && grid.has_temperature());
+        assert_near(grid.max_density(), 0.75);
+        let b = grid.bounds();
+        assert_near(b.min().x(), 1.0);
+        assert_near(b.max().x(), 2.0);
+
+        // Voxel centers are at x = 1.25 and 1.75
+        let first = Point3::new(1.25, 0.25, 0.25);
+        let second = Point3::new(1.75, 0.25, 0.25);
+        assert_near(grid.density(first), 0.25);
+        assert_near(grid.temperature(first), 1000.0);
+        assert_near(grid.emission(first), 3.0);
+        assert_near(grid.density(second), 0.75);
+        assert_near(grid.temperature(second), 2000.0);
+        assert_near(grid.emission(second), 4.0);
+    }
+
+    #[test]
+    fn malformed_files_are_errors() {
+        // Missing `data`, or `size`
+        assert_eq!(error_line("size 1 1 1\n"), 1);
+        assert_eq!(error_line("channels density\ndata\n0.5\n"), 2);
+        // Wrong number of values, reported at the end of the file
+        assert_eq!(error_line("size 2 1 1\ndata\n0.5\n"), 3);
+        assert_eq!(
+            error_line("size 1 1 1\nchannels density emission\ndata\n0.5 1 2\n"),
+            4
+        );
+        // A duplicate channel, or none for density
+        assert_eq!(error_line("size 1 1 1\nchannels density density\n"), 2);
+        assert_eq!(error_line("size 1 1 1\nchannels emission\n"), 2);
+        // Negative densities, though other channels may be negative
+        assert_eq!(error_line("size 2 1 1\ndata\n0.5\n-0.5\n"), 4);
+        parse("size 1 1 1\nchannels density emission\ndata\n0.5 -1\n").unwrap();
+    }
+
+    #[test]
+    fn oversized_grids_are_errors() {
+        assert_eq!(error_line("size 4294967296 4294967297 1\ndata\n"), 1);
+        assert_eq!(error_line("size 4294967296 4294967296 2\ndata\n"), 1);
+        // Few enough voxels, but too many values with three channels
+        let text = format!(
+            "size {} 1 1\nchannels density emission temperature\ndata\n",
+            usize::MAX / 2
+        );
+        assert_eq!(error_line(&text), 3);
+    }
+
+    #[test]
+    fn lookup_interpolates_between_centers() {
+        // 2 x 2 x 1 voxels, with a density of x + 2y at the voxel at (x, y)
+        let grid = VoxelGrid::new(
+            [2, 2, 1],
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            vec![0.0, 1.0, 2.0, 3.0],
+        );
+        assert_near(grid.density(Point3::new(0.5, 0.5, 0.5)), 0.0);
+        assert_near(grid.density(Point3::new(1.5, 1.5, 0.5)), 3.0);
+        // Halfway between all four centers, and a quarter of the way
+        assert_near(grid.density(Point3::new(1.0, 1.0, 0.5)), 1.5);
+        assert_near(grid.density(Point3::new(0.75, 0.5, 0.5)), 0.25);
+        assert_near(grid.density(Point3::new(1.0, 0.75, 0.2)), 1.0);
+    }
+
+    #[test]
+    fn lookup_clamps_at_edges_and_is_zero_outside() {
+        let grid = VoxelGrid::new([2, 1, 1], Point3::new(-1.0, 0.0, 0.0), 1.0, vec![2.0, 4.0]);
+        // Between the outer centers and the faces of the grid
+        assert_near(grid.density(Point3::new(-0.9, 0.5, 0.5)), 2.0);
+        assert_near(grid.density(Point3::new(0.95, 0.1, 0.9)), 4.0);
+        assert_near(grid.density(Point3::new(1.0, 0.5, 0.5)), 4.0);
+        // Outside, along each axis
+        assert_eq!(grid.density(Point3::new(-1.01, 0.5, 0.5)), 0.0);
+        assert_eq!(grid.density(Point3::new(0.5, 1.01, 0.5)), 0.0);
+        assert_eq!(grid.density(Point3::new(0.5, 0.5, -0.01)), 0.0);
+        assert_eq!(grid.emission(Point3::new(0.5, 0.5, 0.5)), 0.0);
+    }
+}
@@ -1,2 +1,2 @@
 #create image
-cargo run > image.ppm
\ No newline at end of file
+cargo run --release -- -o image.ppm
\ No newline at end of file